@@ -0,0 +1,368 @@
+use clap::{builder::PossibleValuesParser, Args, Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
+
+use crate::completions::{complete_task_id, SHELLS};
+use crate::dates::parse_timestamp;
+use crate::ds::{ImportMode, Priority};
+use crate::query::SortKey;
+use crate::transfer::Format;
+use crate::utils::{parse_prefix, parse_tag, parse_task_id, parse_task_range, TaskId, TaskRange};
+
+/// Keep track of what you get done, one day at a time
+///
+/// Run without a command to see today's tasks and statistics.
+#[derive(Parser)]
+#[command(name = "progress", version)]
+pub struct Cli {
+    /// Use the store at <path> (file or directory)
+    #[arg(long, global = true, value_name = "path")]
+    pub store: Option<String>,
+
+    /// How long to wait for another process to release the store
+    #[arg(long, global = true, value_name = "seconds")]
+    pub lock_timeout: Option<String>,
+
+    /// Print the output as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Only show tasks in <project> (a name or prefix), and add new ones to it
+    #[arg(long, global = true, value_name = "project")]
+    pub project: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add a new task
+    ///
+    /// Words like +infra in the label become tags.
+    #[command(long_flag = "add")]
+    Add {
+        label: String,
+
+        /// Tag the task, can be repeated
+        #[arg(long = "tag", short, value_parser = parse_tag, value_name = "tag")]
+        tags: Vec<String>,
+
+        /// How important the task is
+        #[arg(long, short, value_enum, default_value_t = Priority::None)]
+        priority: Priority,
+
+        /// When the task is due, like 2026-11-02 14:00, tomorrow or fri 9am
+        #[arg(long, value_parser = parse_timestamp, value_name = "date")]
+        due: Option<i64>,
+
+        /// Make it a subtask of the task with this id, like TSK-4
+        #[arg(
+            long,
+            value_parser = parse_task_id,
+            value_name = "task-id",
+            add = ArgValueCompleter::new(complete_task_id)
+        )]
+        parent: Option<TaskId>,
+    },
+
+    /// Show a task, or change it
+    #[command(long_flag = "task")]
+    Task {
+        /// Task id, like TSK-1
+        #[arg(value_parser = parse_task_id, add = ArgValueCompleter::new(complete_task_id))]
+        id: TaskId,
+
+        #[command(subcommand)]
+        action: Option<TaskAction>,
+    },
+
+    /// List tasks, optionally filtered by a query
+    ///
+    /// Terms: status:open|done, priority:high|medium|low|none, created:<date>,
+    /// checked:<date>, due:<date>, tag:<tag>, label~<regex>.
+    /// A date is YYYY-MM-DD with an optional >, >=, < or <= in front, or one of
+    /// today, yesterday, this-week, last-week, this-month, last-month.
+    /// Combine terms with and, or, not and parentheses; terms next to each
+    /// other must all match.
+    List {
+        /// e.g. status:open and created:>2026-01-01
+        query: Vec<String>,
+
+        #[arg(long, value_enum, default_value_t = SortKey::Id)]
+        sort: SortKey,
+
+        /// Reverse the sort order
+        #[arg(long)]
+        reverse: bool,
+
+        /// Show at most <count> tasks
+        #[arg(long, value_name = "count")]
+        limit: Option<usize>,
+    },
+
+    /// Find tasks by their label, forgiving typos
+    Search {
+        #[arg(required = true)]
+        terms: Vec<String>,
+
+        /// Show at most <count> tasks
+        #[arg(long, value_name = "count", default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Edit tasks in $VISUAL or $EDITOR
+    ///
+    /// Change labels, check or uncheck tasks, delete lines to remove tasks and
+    /// add lines for new ones. Changes follow the same rules as the other commands.
+    Edit {
+        /// Task ids like TSK-3
+        #[arg(
+            value_parser = parse_task_id,
+            required_unless_present = "today",
+            add = ArgValueCompleter::new(complete_task_id)
+        )]
+        ids: Vec<TaskId>,
+
+        /// Every task created today
+        #[arg(long)]
+        today: bool,
+    },
+
+    /// Open a full-screen view to triage tasks with the keyboard
+    Tui,
+
+    /// Start an interactive prompt that takes the same commands
+    Shell,
+
+    /// Mark tasks as done
+    Check(Selection),
+
+    /// Mark tasks as not done
+    Uncheck(Selection),
+
+    /// Remove tasks
+    Remove(Selection),
+
+    /// List projects, or create, rename and archive them
+    ///
+    /// Each project is a separate list whose task ids start with its own
+    /// prefix, like WORK-3. Tasks outside of any project are in the inbox.
+    Project {
+        #[command(subcommand)]
+        action: Option<ProjectAction>,
+    },
+
+    /// Show a one-line summary of pending tasks
+    #[command(long_flag = "minimal")]
+    Minimal,
+
+    /// Show which store is in use and why
+    Where,
+
+    /// Show what a date expression like `next fri 9am` resolves to
+    ///
+    /// Accepts now, today, tomorrow, yesterday, eod, eow, eom, weekdays (fri,
+    /// this fri, next fri), durations (in 3d, in 2 weeks, in 90 min) and
+    /// YYYY-MM-DD, with an optional time like 14:00 or 9am after a day.
+    Date {
+        #[arg(required = true)]
+        expression: Vec<String>,
+    },
+
+    /// Check the store for problems
+    Doctor {
+        /// Rewrite the store with the problems repaired
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Export every task
+    Export {
+        #[arg(long, value_enum, ignore_case = true, default_value_t = Format::Json)]
+        format: Format,
+
+        /// Write to <file> instead of printing
+        #[arg(long, value_name = "file")]
+        output: Option<String>,
+    },
+
+    /// Import tasks from an export
+    Import {
+        file: String,
+
+        /// Guessed from the file's extension when left out
+        #[arg(long, value_enum, ignore_case = true)]
+        format: Option<Format>,
+
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// Load it from your shell's startup file, e.g.
+    /// `source <(progress completions bash)` in ~/.bashrc
+    Completions {
+        #[arg(value_parser = PossibleValuesParser::new(SHELLS))]
+        shell: String,
+    },
+}
+
+/// The tasks a bulk command changes: every task picked by an id, a range or
+/// a filter
+#[derive(Args)]
+pub struct Selection {
+    /// Task ids like TSK-3, or ranges like TSK-8..TSK-12
+    #[arg(
+        value_parser = parse_task_range,
+        required_unless_present_any = ["today", "carry_over"],
+        add = ArgValueCompleter::new(complete_task_id)
+    )]
+    pub ids: Vec<TaskRange>,
+
+    /// Every task created today
+    #[arg(long)]
+    pub today: bool,
+
+    /// Every unchecked task from before today
+    #[arg(long)]
+    pub carry_over: bool,
+
+    /// Save nothing unless every task can be changed
+    #[arg(long)]
+    pub atomic: bool,
+}
+
+impl Command {
+    /// Whether the command has to hold the store to itself while it runs.
+    /// `edit`, `tui` and `shell` only take it around each change they make.
+    pub fn changes_store(&self) -> bool {
+        return match self {
+            Command::Add { .. }
+            | Command::Import { .. }
+            | Command::Check(_)
+            | Command::Uncheck(_)
+            | Command::Remove(_) => true,
+            Command::Task { action, .. } => action.is_some(),
+            Command::Project { action } => !matches!(action, None | Some(ProjectAction::List)),
+            _ => false,
+        };
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ProjectAction {
+    /// List projects with how many tasks they hold
+    List,
+
+    /// Create a project
+    Create {
+        name: String,
+
+        /// Start task ids with <prefix>, made from the name when left out
+        #[arg(long, value_parser = parse_prefix, value_name = "prefix")]
+        prefix: Option<String>,
+    },
+
+    /// Rename a project, its task ids stay the same
+    Rename {
+        /// The project's name or prefix
+        project: String,
+        name: String,
+    },
+
+    /// Hide a project and its tasks unless asked for with --project
+    Archive {
+        /// The project's name or prefix
+        project: String,
+    },
+
+    /// Bring back an archived project
+    Unarchive {
+        /// The project's name or prefix
+        project: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TaskAction {
+    /// Mark the task as done
+    #[command(long_flag = "check")]
+    Check,
+
+    /// Mark the task as not done
+    #[command(long_flag = "uncheck")]
+    Uncheck,
+
+    /// Remove the task
+    #[command(long_flag = "remove")]
+    Remove,
+
+    /// Change the task's label
+    #[command(long_flag = "rename")]
+    Rename { label: String },
+
+    /// Change how important the task is
+    #[command(long_flag = "priority")]
+    Priority {
+        #[arg(value_enum)]
+        level: Priority,
+    },
+
+    /// Add tags to the task
+    #[command(long_flag = "tag")]
+    Tag {
+        #[arg(required = true, value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+
+    /// Take tags off the task
+    #[command(long_flag = "untag")]
+    Untag {
+        #[arg(required = true, value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+
+    /// Mark the task as waiting on other tasks
+    #[command(long_flag = "block")]
+    Block {
+        /// Tasks that have to be done first
+        #[arg(
+            required = true,
+            value_parser = parse_task_id,
+            value_name = "task-id",
+            add = ArgValueCompleter::new(complete_task_id)
+        )]
+        blockers: Vec<TaskId>,
+    },
+
+    /// Stop the task waiting on other tasks
+    #[command(long_flag = "unblock")]
+    Unblock {
+        #[arg(
+            required = true,
+            value_parser = parse_task_id,
+            value_name = "task-id",
+            add = ArgValueCompleter::new(complete_task_id)
+        )]
+        blockers: Vec<TaskId>,
+    },
+
+    /// Move the task to another project, `inbox` for none
+    #[command(long_flag = "move")]
+    Move {
+        /// The project's name or prefix
+        project: String,
+    },
+
+    /// Set when the task is due, like 2026-11-02 14:00, tomorrow or fri 9am
+    #[command(long_flag = "due")]
+    Due {
+        #[arg(value_parser = parse_timestamp, required_unless_present = "clear")]
+        date: Option<i64>,
+
+        /// Remove the due date instead
+        #[arg(long, conflicts_with = "date")]
+        clear: bool,
+    },
+}
@@ -0,0 +1,363 @@
+use std::{collections::HashSet, path::Path};
+
+use colored::Colorize;
+
+use crate::cli::{Command, ProjectAction, Selection, TaskAction};
+use crate::ds::{BulkAction, Store, Task};
+use crate::edit;
+use crate::error::ProgressError;
+use crate::query::Query;
+use crate::report::{
+    self, DatePreview, Overview, ProjectList, SearchResults, Summary, TaskDetail, TaskList,
+};
+use crate::transfer::{self, Format};
+use crate::tui;
+use crate::utils::{split_tags, TaskId};
+
+/// Runs `command` against an open store, the overview when there's none.
+/// Commands that work without a store are handled before it's opened.
+pub fn execute(
+    store: &mut Store,
+    command: Option<Command>,
+    json: bool,
+) -> Result<(), ProgressError> {
+    let command = match command {
+        Some(command) => command,
+        None => {
+            report::show(&Overview::build(store)?, json);
+            return Ok(());
+        }
+    };
+
+    match command {
+        Command::Minimal => {
+            report::show(&Summary::build(store), json);
+        }
+        Command::List {
+            query,
+            sort,
+            reverse,
+            limit,
+        } => {
+            let query = Query::parse(&query.join(" "))?;
+            report::show(&TaskList::build(store, &query, sort, reverse, limit), json);
+        }
+        Command::Search { terms, limit } => {
+            let results = SearchResults::build(store, &terms.join(" "), limit);
+            report::show(&results, json);
+        }
+        Command::Edit { ids, today } => {
+            let mut tasks = if today { store.tasks_today()? } else { vec![] };
+            for id in ids {
+                let id = store.resolve_task(&id)?;
+                if !tasks.iter().any(|task| task.id == id) {
+                    tasks.push(TaskDetail::build(store, id)?.task);
+                }
+            }
+            return edit::run(store, tasks);
+        }
+        Command::Tui => {
+            return tui::run(store);
+        }
+        Command::Check(selection) => {
+            return change_tasks(store, selection, BulkAction::Check);
+        }
+        Command::Uncheck(selection) => {
+            return change_tasks(store, selection, BulkAction::Uncheck);
+        }
+        Command::Remove(selection) => {
+            return change_tasks(store, selection, BulkAction::Remove);
+        }
+        Command::Add {
+            label,
+            tags,
+            priority,
+            due,
+            parent,
+        } => {
+            let (label, label_tags) = split_tags(&label);
+            if label.is_empty() {
+                return Err(ProgressError::Usage("Need to include label".to_string()));
+            }
+            let parent = parent.map(|id| store.resolve_task(&id)).transpose()?;
+            let id = store.create_task(Task {
+                label,
+                priority,
+                date_due: due,
+                tags: tags.into_iter().chain(label_tags).collect(),
+                parent,
+                ..Task::default()
+            })?;
+            store.save()?;
+            println!(
+                "Task ({}) added to store",
+                store.task_key(id).to_lowercase()
+            );
+        }
+        Command::Task { id, action } => {
+            let id = store.resolve_task(&id)?;
+            let action = match action {
+                Some(action) => action,
+                None => {
+                    report::show(&TaskDetail::build(store, id)?, json);
+                    return Ok(());
+                }
+            };
+
+            match action {
+                TaskAction::Remove => {
+                    store.remove_task(id)?;
+                }
+                TaskAction::Check => {
+                    let checked = store.toggle_check_task(id, true)?;
+                    if let Some(warning) = store.blocked_warning(&checked) {
+                        println!("{}", format!("{} {}", store.task_key(id), warning).yellow());
+                    }
+                    for parent in checked.parents {
+                        println!(
+                            "{} checked too, all of its subtasks are done",
+                            store.task_key(parent).green()
+                        );
+                    }
+                }
+                TaskAction::Uncheck => {
+                    store.toggle_check_task(id, false)?;
+                }
+                TaskAction::Rename { label } => {
+                    if label.trim().is_empty() {
+                        return Err(ProgressError::Usage("Need to include label".to_string()));
+                    }
+                    store.relabel_task(id, &label)?;
+                }
+                TaskAction::Priority { level } => {
+                    store.prioritize_task(id, level)?;
+                }
+                TaskAction::Tag { tags } => {
+                    store.tag_task(id, &tags, true)?;
+                }
+                TaskAction::Untag { tags } => {
+                    store.tag_task(id, &tags, false)?;
+                }
+                TaskAction::Block { blockers } => {
+                    store.block_task(id, &resolve_tasks(store, &blockers)?, true)?;
+                }
+                TaskAction::Unblock { blockers } => {
+                    store.block_task(id, &resolve_tasks(store, &blockers)?, false)?;
+                }
+                TaskAction::Move { project } => {
+                    store.move_task(id, &project)?;
+                    println!("Task is now {}", store.task_key(id));
+                }
+                TaskAction::Due { date, .. } => {
+                    store.set_due_date(id, date)?;
+                }
+            }
+
+            store.save()?;
+        }
+        Command::Project { action } => {
+            return change_project(store, action.unwrap_or(ProjectAction::List), json);
+        }
+        Command::Export { format, output } => {
+            let tasks: Vec<Task> = store.visible_tasks().cloned().collect();
+            let content = transfer::export(&store.metadata, &tasks, format);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, content)?;
+                    println!("Exported {} task(s) to {}", tasks.len(), path);
+                }
+                None => print!("{}", content),
+            }
+        }
+        Command::Import { file, format, mode } => {
+            let format = match format {
+                Some(format) => format,
+                None => Format::from_path(Path::new(&file)).ok_or_else(|| {
+                    ProgressError::Usage("Can't tell the file's format, pass --format".to_string())
+                })?,
+            };
+
+            let content = std::fs::read_to_string(&file)?;
+            let (metadata, tasks) = transfer::import(&content, format)?;
+            let summary = store.import_tasks(metadata, tasks, mode)?;
+            store.save()?;
+
+            if summary.removed > 0 {
+                println!("Removed {} existing task(s)", summary.removed);
+            }
+            println!("Imported {} task(s)", summary.added.to_string().green());
+            if summary.skipped > 0 {
+                println!("Skipped {} task(s) already in the store", summary.skipped);
+            }
+            for (old_id, new_id) in summary.renumbered {
+                let key = store.task_key(new_id);
+                let prefix = key.trim_end_matches(|c: char| c.is_ascii_digit());
+                println!(
+                    "{}",
+                    format!("{}{} was taken, imported as {}", prefix, old_id, key).yellow()
+                );
+            }
+        }
+        Command::Date { expression } => {
+            return preview_date(&expression, json);
+        }
+        // `run` handles these before a store is opened, so only the shell gets here
+        Command::Where | Command::Doctor { .. } | Command::Completions { .. } | Command::Shell => {
+            return Err(ProgressError::Usage(
+                "This command can't be run inside the shell".to_string(),
+            ));
+        }
+    }
+
+    return Ok(());
+}
+
+/// `progress date`, which doesn't need a store
+pub fn preview_date(expression: &[String], json: bool) -> Result<(), ProgressError> {
+    report::show(&DatePreview::build(&expression.join(" "))?, json);
+    return Ok(());
+}
+
+fn change_project(
+    store: &mut Store,
+    action: ProjectAction,
+    json: bool,
+) -> Result<(), ProgressError> {
+    match action {
+        ProjectAction::List => {
+            report::show(&ProjectList::build(store), json);
+            return Ok(());
+        }
+        ProjectAction::Create { name, prefix } => {
+            let prefix = store.create_project(&name, prefix)?;
+            println!(
+                "Project {} created, its tasks will be {}-<n>",
+                name.trim().green(),
+                prefix
+            );
+        }
+        ProjectAction::Rename { project, name } => {
+            store.rename_project(&project, &name)?;
+            println!("Project renamed to {}", name.trim().green());
+        }
+        ProjectAction::Archive { project } => {
+            store.archive_project(&project, true)?;
+            println!("Project {} archived", project_name(store, &project)?);
+        }
+        ProjectAction::Unarchive { project } => {
+            store.archive_project(&project, false)?;
+            println!("Project {} is back", project_name(store, &project)?);
+        }
+    }
+    return store.save();
+}
+
+fn project_name(store: &Store, project: &str) -> Result<String, ProgressError> {
+    let prefix = store.resolve_project(project)?;
+    return Ok(store
+        .project(&prefix)
+        .map(|project| project.name.clone())
+        .unwrap_or(prefix));
+}
+
+/// The numbers of the tasks `ids` name, failing on the first that names none
+fn resolve_tasks(store: &Store, ids: &[TaskId]) -> Result<Vec<u32>, ProgressError> {
+    return ids.iter().map(|id| store.resolve_task(id)).collect();
+}
+
+/// Applies `action` to every selected task with a single save, and reports
+/// how each one went
+fn change_tasks(
+    store: &mut Store,
+    selection: Selection,
+    action: BulkAction,
+) -> Result<(), ProgressError> {
+    // each task's key, and its number unless the id named no task
+    let mut targets: Vec<(String, Option<u32>)> = vec![];
+    for range in selection.ids {
+        if range.start == range.end {
+            let key = range.start.to_string();
+            targets.push((key, store.resolve_task(&range.start).ok()));
+        } else {
+            // gaps left by removed tasks aren't worth reporting
+            targets.extend(
+                store
+                    .visible_tasks()
+                    .filter(|task| range.contains(task))
+                    .map(|task| (task.key(), Some(task.id))),
+            );
+        }
+    }
+    if selection.today {
+        let tasks = store.tasks_today()?;
+        targets.extend(tasks.iter().map(|task| (task.key(), Some(task.id))));
+    }
+    if selection.carry_over {
+        let tasks = store.carry_over_tasks()?;
+        targets.extend(tasks.iter().map(|task| (task.key(), Some(task.id))));
+    }
+
+    let mut seen = HashSet::new();
+    targets.retain(|(key, _)| seen.insert(key.clone()));
+    if targets.is_empty() {
+        println!("{}", "No tasks selected".yellow());
+        return Ok(());
+    }
+
+    let done = match action {
+        BulkAction::Check => "checked",
+        BulkAction::Uncheck => "unchecked",
+        BulkAction::Remove => "removed",
+    };
+    let ids: Vec<u32> = targets.iter().filter_map(|(_, id)| *id).collect();
+    let snapshot = selection.atomic.then(|| store.tasks.clone());
+    let mut outcomes = store.apply_to_tasks(&ids, action).into_iter();
+    let mut failed = 0;
+    for (key, id) in &targets {
+        let outcome = match id {
+            Some(_) => outcomes.next().expect("an outcome per task").1,
+            None => Err(ProgressError::NotFound(key.clone())),
+        };
+        match outcome {
+            Ok(checked) => {
+                println!("{} {}", key.green(), done);
+                if let Some(warning) = store.blocked_warning(&checked) {
+                    println!("{}", format!("{} {}", key, warning).yellow());
+                }
+                for parent in &checked.parents {
+                    println!(
+                        "{} checked too, all of its subtasks are done",
+                        store.task_key(*parent).green()
+                    );
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{} {}", key.red(), e);
+            }
+        }
+    }
+
+    let total = targets.len();
+    if let Some(snapshot) = snapshot.filter(|_| failed > 0) {
+        // the shell keeps the store open, so the tasks that did change
+        // mustn't be written by its next command
+        store.restore(snapshot)?;
+        return Err(ProgressError::PartialFailure {
+            failed,
+            total,
+            saved: false,
+        });
+    }
+
+    store.save()?;
+    if failed > 0 {
+        return Err(ProgressError::PartialFailure {
+            failed,
+            total,
+            saved: true,
+        });
+    }
+    println!("{} task(s) {}", total, done);
+    return Ok(());
+}
@@ -0,0 +1,103 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    time::Duration,
+};
+
+use clap::CommandFactory;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use clap_complete::CompleteEnv;
+
+use crate::cli::Cli;
+use crate::ds::Store;
+use crate::error::ProgressError;
+use crate::location;
+use crate::lock::LockMode;
+
+/// Environment variable the completion scripts set when they call back into
+/// `progress` for candidates
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+pub const SHELLS: [&str; 5] = ["bash", "elvish", "fish", "powershell", "zsh"];
+
+/// Answers a completion script's call for candidates and exits; does nothing
+/// on a normal run
+pub fn complete_if_requested() {
+    let command = Cli::command();
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+
+    // the completion engine doesn't know the `--task` style spellings, so the
+    // words before the one being completed are passed as their subcommand
+    let last = args.len().saturating_sub(1);
+    for arg in &mut args[..last] {
+        if let Some(name) = arg
+            .to_str()
+            .and_then(|arg| subcommand_for_flag(&command, arg))
+        {
+            *arg = name.into();
+        }
+    }
+
+    let current_dir = std::env::current_dir().ok();
+    let completed = CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV)
+        .try_complete(args, current_dir.as_deref())
+        .unwrap_or_else(|e| e.exit());
+    if completed {
+        std::process::exit(0);
+    }
+}
+
+fn subcommand_for_flag(command: &clap::Command, arg: &str) -> Option<String> {
+    let flag = arg.strip_prefix("--")?;
+    for subcommand in command.get_subcommands() {
+        if subcommand.get_long_flag() == Some(flag) {
+            return Some(subcommand.get_name().to_string());
+        }
+        if let Some(name) = subcommand_for_flag(subcommand, arg) {
+            return Some(name);
+        }
+    }
+    return None;
+}
+
+/// Writes the script that registers completions with `shell`
+pub fn write_script(shell: &str) -> Result<(), ProgressError> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .ok_or_else(|| ProgressError::Usage(format!("Unknown shell \"{}\"", shell)))?;
+
+    // the script calls this same binary back, wherever it's installed
+    let binary = std::env::current_exe()?;
+    completer.write_registration(
+        COMPLETE_ENV,
+        "progress",
+        "progress",
+        &binary.to_string_lossy(),
+        &mut io::stdout(),
+    )?;
+    return Ok(());
+}
+
+/// Offers the ids of open tasks, with their labels as descriptions. Runs on
+/// every tab press, so it never waits for a lock and stays quiet on errors.
+pub fn complete_task_id(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy().to_lowercase();
+    let store = location::resolve(None)
+        .and_then(|location| Store::open(&location.path, LockMode::Shared, Duration::ZERO));
+    let store = match store {
+        Ok(store) => store,
+        Err(_) => return vec![],
+    };
+
+    return store
+        .tasks
+        .iter()
+        .filter(|task| !task.done)
+        .map(|task| (task.key(), task))
+        .filter(|(id, _)| id.to_lowercase().starts_with(&current))
+        .map(|(id, task)| CompletionCandidate::new(id).help(Some(task.label.clone().into())))
+        .collect();
+}
@@ -0,0 +1,225 @@
+use chrono::{
+    DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    Timelike, Weekday,
+};
+
+use crate::error::ProgressError;
+
+const HELP: &str = "try a date like 2026-11-02 14:00, tomorrow, fri 9am, next mon, in 3d or eod";
+
+/// Resolves a date expression against the current local time, see `parse_at`
+pub fn parse(expression: &str) -> Result<DateTime<Local>, ProgressError> {
+    let now = Local::now().naive_local().with_nanosecond(0).unwrap();
+    let resolved = parse_at(expression, now)?;
+    // skipped by a daylight saving change
+    return resolved
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| invalid(expression, "that time doesn't exist here"));
+}
+
+/// For clap: a date expression as a Unix timestamp
+pub fn parse_timestamp(expression: &str) -> Result<i64, ProgressError> {
+    return Ok(parse(expression)?.timestamp());
+}
+
+/// Resolves a date expression, in local time, counting from `now`:
+///
+/// - `now`, `today`, `tomorrow`, `yesterday`
+/// - `eod`, `eow` and `eom` for the end of the day, week (Sunday) or month
+/// - a weekday like `fri` or `friday` for the next one after today, `this fri`
+///   for the one in the current week and `next fri` for the one in next week
+/// - `in 3d`, `in 2 weeks`, `in 90 min`, counted from now
+/// - `2026-11-02`
+///
+/// Days can be followed by a time like `14:00`, `9am` or `5:30pm`. Without
+/// one they resolve to the end of the day.
+pub fn parse_at(expression: &str, now: NaiveDateTime) -> Result<NaiveDateTime, ProgressError> {
+    let lowercase = expression.trim().to_lowercase();
+    let words: Vec<&str> = lowercase.split_whitespace().collect();
+    let fail = |reason: &str| invalid(expression, reason);
+    let today = now.date();
+
+    let (date, rest) = match words.as_slice() {
+        [] => return Err(fail("it's empty")),
+        ["now"] => return Ok(now),
+        ["in", amount @ ..] => {
+            let duration =
+                parse_duration(&amount.join(" ")).ok_or_else(|| fail("unknown duration"))?;
+            return now
+                .checked_add_signed(duration)
+                .ok_or_else(|| fail("too far away"));
+        }
+        ["today", rest @ ..] | ["eod", rest @ ..] => (today, rest),
+        ["tomorrow", rest @ ..] => (today + Days::new(1), rest),
+        ["yesterday", rest @ ..] => (today - Days::new(1), rest),
+        ["eow", rest @ ..] => (week_start(today) + Days::new(6), rest),
+        ["eom", rest @ ..] => {
+            let month_start = today.with_day(1).unwrap();
+            (month_start + Months::new(1) - Days::new(1), rest)
+        }
+        ["this", day, rest @ ..] => {
+            let weekday = parse_weekday(day).ok_or_else(|| fail("expected a weekday"))?;
+            (
+                week_start(today) + Days::new(weekday.num_days_from_monday() as u64),
+                rest,
+            )
+        }
+        ["next", day, rest @ ..] => {
+            let weekday = parse_weekday(day).ok_or_else(|| fail("expected a weekday"))?;
+            let offset = 7 + weekday.num_days_from_monday() as u64;
+            (week_start(today) + Days::new(offset), rest)
+        }
+        [word, rest @ ..] => {
+            if let Some(weekday) = parse_weekday(word) {
+                let ahead = weekday.days_since(today.weekday());
+                (
+                    today + Days::new(if ahead == 0 { 7 } else { ahead as u64 }),
+                    rest,
+                )
+            } else if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                (date, rest)
+            } else {
+                return Err(fail("unknown date"));
+            }
+        }
+    };
+
+    let time = match rest {
+        [] => NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        _ => parse_time(&rest.join(" ")).ok_or_else(|| fail("unknown time"))?,
+    };
+    return Ok(date.and_time(time));
+}
+
+fn invalid(expression: &str, reason: &str) -> ProgressError {
+    return ProgressError::Usage(format!(
+        "Can't read the date \"{}\": {}, {}",
+        expression, reason, HELP
+    ));
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    return date - Days::new(date.weekday().num_days_from_monday() as u64);
+}
+
+/// `fri` or `friday`
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    return word.parse::<Weekday>().ok();
+}
+
+/// `3d`, `3 days`, `2w`, `4h`, `90min`, `1mo`
+fn parse_duration(text: &str) -> Option<TimeDelta> {
+    let text = text.replace(' ', "");
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(split);
+    let amount = amount.parse::<i64>().ok()?;
+
+    match unit {
+        "min" | "mins" | "minute" | "minutes" => return TimeDelta::try_minutes(amount),
+        "h" | "hr" | "hrs" | "hour" | "hours" => return TimeDelta::try_hours(amount),
+        "d" | "day" | "days" => return TimeDelta::try_days(amount),
+        "w" | "wk" | "wks" | "week" | "weeks" => return TimeDelta::try_weeks(amount),
+        // months vary in length, a month is 30 days here
+        "mo" | "month" | "months" => return TimeDelta::try_days(amount.checked_mul(30)?),
+        _ => return None,
+    }
+}
+
+/// `14:00`, `9am`, `5:30pm`, `12pm`
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    let text = text.replace(' ', "");
+    let (clock, offset) = if let Some(clock) = text.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = text.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (text.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour, minute.parse::<u32>().ok()?),
+        Some(_) => return None,
+        None if offset.is_some() => (clock, 0),
+        None => return None,
+    };
+    let mut hour = hour.parse::<u32>().ok()?;
+    if let Some(offset) = offset {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour = hour % 12 + offset;
+    }
+    return NaiveTime::from_hms_opt(hour, minute, 0);
+}
+
+/// The local day a timestamp falls on
+pub fn local_date(timestamp: i64) -> Option<NaiveDate> {
+    return DateTime::from_timestamp(timestamp, 0)
+        .map(|date| date.with_timezone(&Local).date_naive());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(expression: &str) -> String {
+        // a Wednesday
+        let now = NaiveDate::from_ymd_opt(2026, 1, 14)
+            .unwrap()
+            .and_hms_opt(10, 30, 0)
+            .unwrap();
+        return parse_at(expression, now)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+    }
+
+    #[test]
+    fn resolves_days() {
+        assert_eq!(resolve("now"), "2026-01-14 10:30");
+        assert_eq!(resolve("eod"), "2026-01-14 23:59");
+        assert_eq!(resolve("Tomorrow"), "2026-01-15 23:59");
+        assert_eq!(resolve("eow"), "2026-01-18 23:59");
+        assert_eq!(resolve("eom"), "2026-01-31 23:59");
+        assert_eq!(resolve("2026-11-02 14:00"), "2026-11-02 14:00");
+        assert_eq!(resolve("2026-11-02"), "2026-11-02 23:59");
+    }
+
+    #[test]
+    fn resolves_weekdays() {
+        assert_eq!(resolve("fri"), "2026-01-16 23:59");
+        assert_eq!(resolve("wednesday"), "2026-01-21 23:59");
+        assert_eq!(resolve("this mon"), "2026-01-12 23:59");
+        assert_eq!(resolve("next mon"), "2026-01-19 23:59");
+        assert_eq!(resolve("next fri 9am"), "2026-01-23 09:00");
+    }
+
+    #[test]
+    fn resolves_times_and_durations() {
+        assert_eq!(resolve("today 5:30pm"), "2026-01-14 17:30");
+        assert_eq!(resolve("tomorrow 12am"), "2026-01-15 00:00");
+        assert_eq!(resolve("tomorrow 12pm"), "2026-01-15 12:00");
+        assert_eq!(resolve("in 3d"), "2026-01-17 10:30");
+        assert_eq!(resolve("in 2 weeks"), "2026-01-28 10:30");
+        assert_eq!(resolve("in 90 min"), "2026-01-14 12:00");
+    }
+
+    #[test]
+    fn rejects_unknown_expressions() {
+        let now = Local::now().naive_local();
+        for expression in [
+            "",
+            "someday",
+            "next",
+            "next week",
+            "in 3 fortnights",
+            "fri 25:00",
+            "today 13pm",
+            "2026-02-30",
+            "mo",
+        ] {
+            assert!(parse_at(expression, now).is_err(), "{}", expression);
+        }
+    }
+}
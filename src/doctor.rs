@@ -0,0 +1,656 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    time::Duration,
+};
+
+use colored::Colorize;
+
+use crate::ds::{
+    sibling_path, waits_on, Metadata, Project, Task, INBOX_PREFIX, METADATA_FIELDS, PROJECT_FIELDS,
+    TASK_FIELDS,
+};
+use crate::error::ProgressError;
+use crate::lock::{LockMode, StoreLock};
+use crate::migrations::{self, STORE_VERSION};
+use crate::repository::{is_sqlite, TaskRepository};
+use crate::text_repository::TextRepository;
+
+struct Problem {
+    line: Option<usize>,
+    message: String,
+}
+
+enum Block {
+    Metadata(Metadata),
+    Task(Task),
+    Project(Project),
+}
+
+struct OpenBlock {
+    block: Block,
+    start: usize,
+    pointer: u8,
+    broken: bool,
+}
+
+/// Everything that could be salvaged from a store, and what was wrong with it
+struct Scan {
+    metadata: Option<Metadata>,
+    tasks: Vec<(Task, usize)>,
+    projects: Vec<(Project, usize)>,
+    problems: Vec<Problem>,
+}
+
+/// Checks the store for damage and inconsistencies, and with `fix` rewrites
+/// it with everything that could be salvaged
+pub fn run(path: &Path, fix: bool, timeout: Duration) -> Result<(), ProgressError> {
+    if is_sqlite(path) {
+        return Err(ProgressError::Usage(
+            "doctor checks text stores, SQLite stores keep their own integrity".to_string(),
+        ));
+    }
+
+    let mode = if fix {
+        LockMode::Exclusive
+    } else {
+        LockMode::Shared
+    };
+    let _lock = StoreLock::acquire(path, mode, timeout)?;
+
+    println!("Checking {}", path.display());
+    if !path.exists() {
+        println!("{}", "No store here yet, nothing to check".green());
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let (version, body, first_line) = migrations::split_header(&content)?;
+    if version > STORE_VERSION {
+        return Err(ProgressError::UnsupportedVersion {
+            found: version,
+            supported: STORE_VERSION,
+        });
+    }
+    let (body, line_numbers) = migrations::migrate(body, version, first_line)?;
+
+    let mut scan = scan(&body, &line_numbers);
+    let metadata = check(&mut scan);
+
+    if scan.problems.is_empty() {
+        println!("{}", "No problems found".green());
+        return Ok(());
+    }
+
+    for problem in &scan.problems {
+        match problem.line {
+            Some(line) => println!("{} {}", format!("line {}:", line).yellow(), problem.message),
+            None => println!("{} {}", "store:".yellow(), problem.message),
+        }
+    }
+
+    if !fix {
+        println!(
+            "\n{} problem(s) found. Run `progress doctor --fix` to rewrite a repaired store.",
+            scan.problems.len().to_string().red().bold()
+        );
+        return Ok(());
+    }
+
+    let tasks = scan.tasks.into_iter().map(|(task, _)| task).collect();
+    TextRepository::with_tasks(path, tasks).commit(&metadata)?;
+    println!(
+        "\n{} The previous version is kept at {}",
+        "Repaired store saved.".green().bold(),
+        sibling_path(path, "bak").display()
+    );
+    return Ok(());
+}
+
+/// Walks the store like `Store::open` does, but keeps going past bad lines
+fn scan(body: &str, line_numbers: &[usize]) -> Scan {
+    let mut scan = Scan {
+        metadata: None,
+        tasks: vec![],
+        projects: vec![],
+        problems: vec![],
+    };
+    let mut current: Option<OpenBlock> = None;
+
+    for (index, line) in body.split('\n').enumerate() {
+        let line_number = line_numbers[index];
+        if line.is_empty() {
+            continue;
+        }
+
+        match line {
+            ":metadata" | ":task" | ":project" => {
+                if let Some(open) = current.take() {
+                    scan.problems.push(Problem {
+                        line: Some(open.start),
+                        message: "block is missing its :end".to_string(),
+                    });
+                    close(&mut scan, open);
+                }
+
+                let block = match line {
+                    ":task" => Block::Task(Task::default()),
+                    ":project" => Block::Project(Project::default()),
+                    _ => Block::Metadata(Metadata::default()),
+                };
+                current = Some(OpenBlock {
+                    block,
+                    start: line_number,
+                    pointer: 0,
+                    broken: false,
+                });
+            }
+            ":end" => match current.take() {
+                Some(open) => close(&mut scan, open),
+                None => scan.problems.push(Problem {
+                    line: Some(line_number),
+                    message: ":end without a block to close".to_string(),
+                }),
+            },
+            _ => {
+                let open = match current.as_mut() {
+                    Some(open) => open,
+                    None => {
+                        scan.problems.push(Problem {
+                            line: Some(line_number),
+                            message: format!("orphaned line outside of any block: {}", line),
+                        });
+                        continue;
+                    }
+                };
+
+                let read = match &mut open.block {
+                    Block::Metadata(metadata) => metadata.read_field(open.pointer, line),
+                    Block::Task(task) => task.read_field(open.pointer, line),
+                    Block::Project(project) => project.read_field(open.pointer, line),
+                };
+                if let Err(reason) = read {
+                    scan.problems.push(Problem {
+                        line: Some(line_number),
+                        message: format!("{}: {}", reason, line),
+                    });
+                    open.broken = true;
+                }
+                open.pointer = open.pointer.saturating_add(1);
+            }
+        }
+    }
+
+    if let Some(open) = current.take() {
+        scan.problems.push(Problem {
+            line: Some(open.start),
+            message: "block is missing its :end".to_string(),
+        });
+        close(&mut scan, open);
+    }
+
+    return scan;
+}
+
+/// Keeps a finished block if all of its lines were read, otherwise reports it
+fn close(scan: &mut Scan, open: OpenBlock) {
+    match open.block {
+        Block::Metadata(metadata) => {
+            if open.broken || open.pointer != METADATA_FIELDS {
+                scan.problems.push(Problem {
+                    line: Some(open.start),
+                    message: "unreadable metadata block, it will be rebuilt".to_string(),
+                });
+            } else if scan.metadata.is_some() {
+                scan.problems.push(Problem {
+                    line: Some(open.start),
+                    message: "extra metadata block, it will be dropped".to_string(),
+                });
+            } else {
+                scan.metadata = Some(metadata);
+            }
+        }
+        Block::Task(task) => {
+            if open.broken || open.pointer != TASK_FIELDS {
+                scan.problems.push(Problem {
+                    line: Some(open.start),
+                    message:
+                        "orphaned task block with missing or unreadable lines, it will be dropped"
+                            .to_string(),
+                });
+            } else {
+                scan.tasks.push((task, open.start));
+            }
+        }
+        Block::Project(project) => {
+            if open.broken || open.pointer != PROJECT_FIELDS {
+                scan.problems.push(Problem {
+                    line: Some(open.start),
+                    message: "unreadable project block, it will be rebuilt from its tasks"
+                        .to_string(),
+                });
+            } else {
+                scan.projects.push((project, open.start));
+            }
+        }
+    }
+}
+
+/// Looks for inconsistencies between the salvaged tasks and fixes them in the
+/// scan, returning the metadata a repaired store should have
+fn check(scan: &mut Scan) -> Metadata {
+    let mut metadata = match scan.metadata.take() {
+        Some(metadata) => metadata,
+        None => {
+            scan.problems.push(Problem {
+                line: None,
+                message: "no metadata block".to_string(),
+            });
+            Metadata::default()
+        }
+    };
+
+    let max_id = scan.tasks.iter().map(|(task, _)| task.id).max();
+    let mut next_id = max_id
+        .map(|id| id + 1)
+        .unwrap_or(0)
+        .max(metadata.last_task_id);
+
+    // duplicate ids, the first task keeps its id and the rest get new ones
+    let mut seen: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for (task, line) in scan.tasks.iter_mut() {
+        let lines = seen.entry(task.id).or_default();
+        lines.push(*line);
+        if lines.len() > 1 {
+            scan.problems.push(Problem {
+                line: Some(*line),
+                message: format!(
+                    "{} is already used by the task at line {}, it will become {}-{}",
+                    task.key(),
+                    lines[0],
+                    task.prefix(),
+                    next_id
+                ),
+            });
+            task.id = next_id;
+            next_id += 1;
+        }
+    }
+
+    // last_task_id is the id the next task gets, so it must be past every id
+    if let Some(max_id) = max_id {
+        if metadata.last_task_id <= max_id {
+            scan.problems.push(Problem {
+                line: None,
+                message: format!(
+                    "last_task_id is {} but task number {} already exists, new tasks would reuse ids",
+                    metadata.last_task_id, max_id
+                ),
+            });
+        }
+    }
+    metadata.last_task_id = next_id;
+
+    for (task, line) in scan.tasks.iter_mut() {
+        if task.done && task.date_checked.is_none() {
+            scan.problems.push(Problem {
+                line: Some(*line),
+                message: format!(
+                    "{} is done but has no check date, it will use its creation date",
+                    task.key()
+                ),
+            });
+            task.date_checked = Some(task.date_created);
+        } else if !task.done && task.date_checked.is_some() {
+            scan.problems.push(Problem {
+                line: Some(*line),
+                message: format!(
+                    "{} is not done but has a check date, it will be cleared",
+                    task.key()
+                ),
+            });
+            task.date_checked = None;
+        }
+    }
+
+    // a prefix names one project, the first block using it wins
+    metadata.projects.clear();
+    for (project, line) in scan.projects.drain(..) {
+        let taken = project.prefix == INBOX_PREFIX
+            || metadata
+                .projects
+                .iter()
+                .any(|existing| existing.prefix == project.prefix);
+        if taken {
+            scan.problems.push(Problem {
+                line: Some(line),
+                message: format!(
+                    "project \"{}\" uses the prefix {}, which is taken, it will be dropped",
+                    project.name, project.prefix
+                ),
+            });
+        } else {
+            metadata.projects.push(project);
+        }
+    }
+    for (task, line) in scan.tasks.iter() {
+        let prefix = match &task.project {
+            Some(prefix) => prefix,
+            None => continue,
+        };
+        let known = prefix == INBOX_PREFIX
+            || metadata
+                .projects
+                .iter()
+                .any(|project| &project.prefix == prefix);
+        if !known {
+            scan.problems.push(Problem {
+                line: Some(*line),
+                message: format!(
+                    "{} is in a project that doesn't exist, a project {} will be created",
+                    task.key(),
+                    prefix
+                ),
+            });
+            metadata.projects.push(Project {
+                name: prefix.to_lowercase(),
+                prefix: prefix.clone(),
+                archived: false,
+            });
+        }
+    }
+
+    // a parent must exist, and following parents must not lead back around
+    let mut parents: BTreeMap<u32, Option<u32>> = scan
+        .tasks
+        .iter()
+        .map(|(task, _)| (task.id, task.parent))
+        .collect();
+    for (task, line) in scan.tasks.iter_mut() {
+        let parent = match task.parent {
+            Some(parent) => parent,
+            None => continue,
+        };
+        let problem = if !parents.contains_key(&parent) {
+            format!(
+                "{} is a subtask of task number {}, which doesn't exist, it will be a top-level task",
+                task.key(),
+                parent
+            )
+        } else if leads_back(&parents, task.id) {
+            format!(
+                "{} is its own parent through its subtasks, it will be a top-level task",
+                task.key()
+            )
+        } else {
+            continue;
+        };
+        scan.problems.push(Problem {
+            line: Some(*line),
+            message: problem,
+        });
+        task.parent = None;
+        parents.insert(task.id, None);
+    }
+
+    // blockers must exist, and no task may end up waiting on itself
+    let mut blockers: BTreeMap<u32, BTreeSet<u32>> = scan
+        .tasks
+        .iter()
+        .map(|(task, _)| (task.id, task.blocked_by.clone()))
+        .collect();
+    for (task, line) in scan.tasks.iter_mut() {
+        for blocker in task.blocked_by.clone() {
+            let problem = if !blockers.contains_key(&blocker) {
+                format!(
+                    "{} is blocked by task number {}, which doesn't exist, the link will be dropped",
+                    task.key(),
+                    blocker
+                )
+            } else if blocker == task.id || waits_on(|id| blockers.get(&id), blocker, task.id) {
+                format!(
+                    "{} ends up blocked by itself through task number {}, the link will be dropped",
+                    task.key(),
+                    blocker
+                )
+            } else {
+                continue;
+            };
+            scan.problems.push(Problem {
+                line: Some(*line),
+                message: problem,
+            });
+            task.blocked_by.remove(&blocker);
+            blockers.insert(task.id, task.blocked_by.clone());
+        }
+    }
+
+    return metadata;
+}
+
+/// Whether following parents up from `id` comes back to `id`
+fn leads_back(parents: &BTreeMap<u32, Option<u32>>, id: u32) -> bool {
+    let mut current = parents.get(&id).copied().flatten();
+    for _ in 0..parents.len() {
+        match current {
+            Some(parent) if parent == id => return true,
+            Some(parent) => current = parents.get(&parent).copied().flatten(),
+            None => return false,
+        }
+    }
+    return false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u32, done: bool, date_checked: Option<i64>) -> Task {
+        return Task {
+            id,
+            done,
+            label: format!("task {}", id),
+            date_created: 100,
+            date_checked,
+            ..Task::default()
+        };
+    }
+
+    fn body(last_task_id: u32, tasks: &[Task]) -> String {
+        let metadata = Metadata {
+            last_task_id,
+            ..Metadata::default()
+        };
+        let content = TextRepository::render(&metadata, tasks);
+        // drop the version header, like `migrations::split_header` does
+        let (_, body) = content.split_once('\n').unwrap();
+        return body.to_string();
+    }
+
+    /// Scans and repairs `body` as if it started on line 2 of the file
+    fn diagnose(body: &str) -> (Scan, Metadata) {
+        let line_numbers: Vec<usize> = (2..).take(body.split('\n').count()).collect();
+        let mut scan = scan(body, &line_numbers);
+        let metadata = check(&mut scan);
+        return (scan, metadata);
+    }
+
+    fn problems(scan: &Scan) -> Vec<(Option<usize>, &str)> {
+        return scan
+            .problems
+            .iter()
+            .map(|problem| (problem.line, problem.message.as_str()))
+            .collect();
+    }
+
+    /// What `--fix` would write, scanned again
+    fn rescan(scan: Scan, metadata: &Metadata) -> (Scan, Metadata) {
+        let tasks: Vec<Task> = scan.tasks.into_iter().map(|(task, _)| task).collect();
+        let content = TextRepository::render(metadata, &tasks);
+        let (_, body) = content.split_once('\n').unwrap();
+        return diagnose(body);
+    }
+
+    #[test]
+    fn healthy_store_has_no_problems() {
+        let (scan, metadata) =
+            diagnose(&body(2, &[task(0, false, None), task(1, true, Some(200))]));
+        assert!(scan.problems.is_empty());
+        assert_eq!(metadata.last_task_id, 2);
+        assert_eq!(scan.tasks.len(), 2);
+    }
+
+    #[test]
+    fn duplicate_ids_are_renumbered() {
+        let (scan, metadata) = diagnose(&body(
+            1,
+            &[
+                task(0, false, None),
+                task(0, false, None),
+                task(1, false, None),
+            ],
+        ));
+
+        // the metadata block takes lines 2-4 and every task 13 lines
+        assert_eq!(
+            problems(&scan),
+            [
+                (
+                    Some(18),
+                    "TSK-0 is already used by the task at line 5, it will become TSK-2"
+                ),
+                (
+                    None,
+                    "last_task_id is 1 but task number 1 already exists, new tasks would reuse ids"
+                ),
+            ]
+        );
+        let ids: Vec<u32> = scan.tasks.iter().map(|(task, _)| task.id).collect();
+        assert_eq!(ids, [0, 2, 1]);
+        assert_eq!(metadata.last_task_id, 3);
+
+        let (scan, metadata) = rescan(scan, &metadata);
+        assert!(scan.problems.is_empty());
+        assert_eq!(metadata.last_task_id, 3);
+    }
+
+    #[test]
+    fn done_flag_and_check_date_agree() {
+        let (scan, metadata) =
+            diagnose(&body(2, &[task(0, true, None), task(1, false, Some(200))]));
+
+        assert_eq!(
+            problems(&scan),
+            [
+                (
+                    Some(5),
+                    "TSK-0 is done but has no check date, it will use its creation date"
+                ),
+                (
+                    Some(18),
+                    "TSK-1 is not done but has a check date, it will be cleared"
+                ),
+            ]
+        );
+        assert_eq!(scan.tasks[0].0.date_checked, Some(100));
+        assert_eq!(scan.tasks[1].0.date_checked, None);
+        assert!(rescan(scan, &metadata).0.problems.is_empty());
+    }
+
+    #[test]
+    fn damaged_blocks_are_dropped() {
+        let mut body = body(1, &[task(0, false, None)]);
+        // no metadata, a stray line, a task with a bad done flag and one
+        // without its :end
+        body = body.replacen(":metadata\n1\n:end\n", "", 1);
+        body.push_str("stray\n");
+        let damaged = self::body(0, &[task(1, false, None)]);
+        body.push_str(
+            &damaged
+                .replacen(":metadata\n0\n:end\n", "", 1)
+                .replacen("[]", "maybe", 1),
+        );
+        body.push_str(":task\n2\n[]\n");
+
+        let (scan, metadata) = diagnose(&body);
+        assert_eq!(
+            problems(&scan),
+            [
+                (Some(15), "orphaned line outside of any block: stray"),
+                (Some(18), "Expected [x] or [] for the done flag: maybe"),
+                (
+                    Some(16),
+                    "orphaned task block with missing or unreadable lines, it will be dropped"
+                ),
+                (Some(29), "block is missing its :end"),
+                (
+                    Some(29),
+                    "orphaned task block with missing or unreadable lines, it will be dropped"
+                ),
+                (None, "no metadata block"),
+                (
+                    None,
+                    "last_task_id is 0 but task number 0 already exists, new tasks would reuse ids"
+                ),
+            ]
+        );
+        assert_eq!(scan.tasks.len(), 1);
+        assert_eq!(metadata.last_task_id, 1);
+        assert!(rescan(scan, &metadata).0.problems.is_empty());
+    }
+
+    #[test]
+    fn cycles_and_missing_links_are_cut() {
+        let mut tasks: Vec<Task> = (0..5).map(|id| task(id, false, None)).collect();
+        tasks[0].parent = Some(1);
+        tasks[1].parent = Some(0);
+        tasks[2].blocked_by = BTreeSet::from([3]);
+        tasks[3].blocked_by = BTreeSet::from([2]);
+        tasks[4].parent = Some(9);
+        tasks[4].blocked_by = BTreeSet::from([4, 9]);
+
+        let (scan, metadata) = diagnose(&body(5, &tasks));
+        assert_eq!(
+            problems(&scan),
+            [
+                (
+                    Some(5),
+                    "TSK-0 is its own parent through its subtasks, it will be a top-level task"
+                ),
+                (
+                    Some(57),
+                    "TSK-4 is a subtask of task number 9, which doesn't exist, it will be a top-level task"
+                ),
+                (
+                    Some(31),
+                    "TSK-2 ends up blocked by itself through task number 3, the link will be dropped"
+                ),
+                (
+                    Some(57),
+                    "TSK-4 ends up blocked by itself through task number 4, the link will be dropped"
+                ),
+                (
+                    Some(57),
+                    "TSK-4 is blocked by task number 9, which doesn't exist, the link will be dropped"
+                ),
+            ]
+        );
+        let links: Vec<(Option<u32>, Vec<u32>)> = scan
+            .tasks
+            .iter()
+            .map(|(task, _)| (task.parent, task.blocked_by.iter().copied().collect()))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                (None, vec![]),
+                (Some(0), vec![]),
+                (None, vec![]),
+                (None, vec![2]),
+                (None, vec![]),
+            ]
+        );
+        assert!(rescan(scan, &metadata).0.problems.is_empty());
+    }
+}
@@ -1,317 +1,1207 @@
 use chrono::{DateTime, Local};
-use colored::Colorize;
+use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{Read, Write},
-    path::{self},
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::utils::format_timestamp_ago;
+use crate::error::ProgressError;
+use crate::lock::{LockMode, StoreLock};
+use crate::migrations::{STORE_VERSION, VERSION_HEADER};
+use crate::repository::{open_repository, TaskQuery, TaskRepository};
+use crate::search::{self, SearchHit, SearchIndex, INDEX_THRESHOLD};
+use crate::utils::{parse_prefix, parse_tag, quote_field, unquote_field, TaskId};
 
-const STORE_FILE: &str = "progress.store";
+pub const STORE_FILE: &str = "progress.store";
 
-enum ParseState {
-    WritingMetadata(u8),
-    WritingTask(u8),
-}
+/// Lines in a `:task` block, between the marker and `:end`
+pub const TASK_FIELDS: u8 = 11;
+/// Lines in the `:metadata` block
+pub const METADATA_FIELDS: u8 = 1;
+/// Lines in a `:project` block
+pub const PROJECT_FIELDS: u8 = 3;
+
+/// Set to 1 or true to check a task once all of its subtasks are done
+pub const AUTO_CHECK_PARENTS_ENV: &str = "PROGRESS_AUTO_CHECK_PARENTS";
+
+/// Tasks outside of any project are in the inbox, with ids like TSK-1
+pub const INBOX: &str = "inbox";
+pub const INBOX_PREFIX: &str = "TSK";
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: u32,
     pub done: bool,
     pub label: String,
     pub date_created: i64,
     pub date_checked: Option<i64>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub date_due: Option<i64>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Prefix of the project the task is in, none for the inbox
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Id of the task this is a subtask of
+    #[serde(default)]
+    pub parent: Option<u32>,
+    /// Ids of the tasks that have to be done before this one
+    #[serde(default)]
+    pub blocked_by: BTreeSet<u32>,
+}
+
+/// How important a task is. Lists show the most important tasks first.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+    #[default]
+    None,
+}
+
+impl Priority {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Medium => "medium",
+            Priority::Low => "low",
+            Priority::None => "none",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Priority> {
+        match name.to_lowercase().as_str() {
+            "high" => Some(Priority::High),
+            "medium" => Some(Priority::Medium),
+            "low" => Some(Priority::Low),
+            "none" => Some(Priority::None),
+            _ => None,
+        }
+    }
 }
 
 impl Task {
-    fn default() -> Self {
+    pub fn default() -> Self {
         return Task {
             id: 0,
             done: false,
             label: "".to_string(),
             date_checked: None,
             date_created: 0,
+            priority: Priority::None,
+            date_due: None,
+            tags: BTreeSet::new(),
+            project: None,
+            parent: None,
+            blocked_by: BTreeSet::new(),
         };
     }
 
-    fn dump(&self, buffer: &mut String) {
+    /// `TSK`, or the prefix of the task's project
+    pub fn prefix(&self) -> &str {
+        return self.project.as_deref().unwrap_or(INBOX_PREFIX);
+    }
+
+    /// The id people see, like TSK-3 or WORK-12
+    pub fn key(&self) -> String {
+        return format!("{}-{}", self.prefix(), self.id);
+    }
+
+    /// Reads the `pointer`th line of a task block into the task
+    pub fn read_field(&mut self, pointer: u8, line: &str) -> Result<(), String> {
+        match pointer {
+            0 => {
+                self.id = line
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| "Expected a task id".to_string())?;
+            }
+            1 => {
+                self.done = match line.trim() {
+                    "[x]" => true,
+                    "[]" => false,
+                    _ => return Err("Expected [x] or [] for the done flag".to_string()),
+                };
+            }
+            2 => {
+                self.label = unquote_field(line)?;
+            }
+            3 => {
+                self.date_created = line
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| "Expected a creation timestamp".to_string())?;
+            }
+            4 => {
+                self.date_checked = match line.trim() {
+                    "-" => None,
+                    n => Some(
+                        n.parse::<i64>()
+                            .map_err(|_| "Expected a check timestamp or -".to_string())?,
+                    ),
+                };
+            }
+            5 => {
+                self.priority = match line.trim() {
+                    "-" => Priority::None,
+                    name => Priority::from_name(name)
+                        .filter(|priority| *priority != Priority::None)
+                        .ok_or_else(|| {
+                            "Expected high, medium, low or - for the priority".to_string()
+                        })?,
+                };
+            }
+            6 => {
+                self.date_due = match line.trim() {
+                    "-" => None,
+                    n => Some(
+                        n.parse::<i64>()
+                            .map_err(|_| "Expected a due timestamp or -".to_string())?,
+                    ),
+                };
+            }
+            7 => {
+                self.tags = match line.trim() {
+                    "-" => BTreeSet::new(),
+                    tags => tags
+                        .split(' ')
+                        .map(|tag| parse_tag(tag).map_err(|e| e.to_string()))
+                        .collect::<Result<_, _>>()?,
+                };
+            }
+            8 => {
+                self.project = match line.trim() {
+                    "-" => None,
+                    prefix => Some(parse_prefix(prefix).map_err(|e| e.to_string())?),
+                };
+            }
+            9 => {
+                self.parent = match line.trim() {
+                    "-" => None,
+                    n => Some(
+                        n.parse::<u32>()
+                            .map_err(|_| "Expected a parent task id or -".to_string())?,
+                    ),
+                };
+            }
+            10 => {
+                self.blocked_by = match line.trim() {
+                    "-" => BTreeSet::new(),
+                    ids => split_ids(ids)
+                        .ok_or_else(|| "Expected blocking task ids or -".to_string())?,
+                };
+            }
+            _ => return Err("Too many lines in task".to_string()),
+        }
+        return Ok(());
+    }
+
+    /// Tags separated by spaces, which tags can't contain
+    pub fn joined_tags(&self) -> String {
+        return self
+            .tags
+            .iter()
+            .map(|tag| tag.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+    }
+
+    /// Ids of the blocking tasks separated by spaces
+    pub fn joined_blocked_by(&self) -> String {
+        return self
+            .blocked_by
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+    }
+
+    pub fn dump(&self, buffer: &mut String) {
         let date_checked = if let Some(n) = &self.date_checked {
             n.to_string()
         } else {
             "-".to_string()
         };
+        let date_due = if let Some(n) = &self.date_due {
+            n.to_string()
+        } else {
+            "-".to_string()
+        };
 
         buffer.push_str(":task\n");
-        buffer.push_str(format!("{}\n", self.id.to_string()).as_str());
+        buffer.push_str(format!("{}\n", self.id).as_str());
         buffer.push_str(if self.done { "[x]\n" } else { "[]\n" });
-        buffer.push_str(format!("{}\n", &self.label).as_str());
+        buffer.push_str(format!("{}\n", quote_field(&self.label)).as_str());
         buffer.push_str(format!("{}\n", &self.date_created).as_str());
-        buffer.push_str(&date_checked);
+        buffer.push_str(format!("{}\n", date_checked).as_str());
+        buffer.push_str(match self.priority {
+            Priority::None => "-",
+            priority => priority.name(),
+        });
+        buffer.push('\n');
+        buffer.push_str(format!("{}\n", date_due).as_str());
+        if self.tags.is_empty() {
+            buffer.push('-');
+        } else {
+            buffer.push_str(&self.joined_tags());
+        }
+        buffer.push('\n');
+        buffer.push_str(self.project.as_deref().unwrap_or("-"));
+        buffer.push('\n');
+        match self.parent {
+            Some(parent) => buffer.push_str(&parent.to_string()),
+            None => buffer.push('-'),
+        }
+        buffer.push('\n');
+        if self.blocked_by.is_empty() {
+            buffer.push('-');
+        } else {
+            buffer.push_str(&self.joined_blocked_by());
+        }
         buffer.push_str("\n:end\n");
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Metadata {
     pub last_task_id: u32,
+    /// Every project but the inbox, in the order they were created
+    #[serde(default)]
+    pub projects: Vec<Project>,
+}
+
+/// A separate list of tasks, whose ids start with its own prefix
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    /// Like WORK for WORK-3, it never changes so ids stay the same
+    pub prefix: String,
+    /// Archived projects are left out unless asked for with `--project`
+    pub archived: bool,
+}
+
+impl Project {
+    /// Reads the `pointer`th line of a project block into the project
+    pub fn read_field(&mut self, pointer: u8, line: &str) -> Result<(), String> {
+        match pointer {
+            0 => {
+                self.name = unquote_field(line)?;
+            }
+            1 => {
+                self.prefix = parse_prefix(line.trim()).map_err(|e| e.to_string())?;
+            }
+            2 => {
+                self.archived = match line.trim() {
+                    "archived" => true,
+                    "-" => false,
+                    _ => return Err("Expected archived or - for the project".to_string()),
+                };
+            }
+            _ => return Err("Too many lines in project".to_string()),
+        }
+        return Ok(());
+    }
+
+    pub fn dump(&self, buffer: &mut String) {
+        buffer.push_str(":project\n");
+        buffer.push_str(format!("{}\n", quote_field(&self.name)).as_str());
+        buffer.push_str(format!("{}\n", self.prefix).as_str());
+        buffer.push_str(if self.archived { "archived\n" } else { "-\n" });
+        buffer.push_str(":end\n");
+    }
 }
 
 impl Metadata {
-    fn dump(&self, buffer: &mut String) {
+    /// Reads the `pointer`th line of the metadata block
+    pub fn read_field(&mut self, pointer: u8, line: &str) -> Result<(), String> {
+        match pointer {
+            0 => {
+                self.last_task_id = line
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| "Expected the last task id".to_string())?;
+            }
+            _ => return Err("Too many lines in metadata".to_string()),
+        }
+        return Ok(());
+    }
+
+    pub fn dump(&self, buffer: &mut String) {
         assert!(buffer.is_empty());
+        buffer.push_str(format!("{} {}\n", VERSION_HEADER, STORE_VERSION).as_str());
         buffer.push_str(":metadata\n");
         buffer.push_str(format!("{}\n", self.last_task_id).as_str());
         buffer.push_str(":end\n");
+        self.projects
+            .iter()
+            .for_each(|project| project.dump(buffer));
     }
 }
 
+/// Whether `id` is blocked by `other`, directly or through other blockers.
+/// `blocked_by` gives the blockers of a task, `None` for a missing one.
+pub fn waits_on<'a>(
+    blocked_by: impl Fn(u32) -> Option<&'a BTreeSet<u32>>,
+    id: u32,
+    other: u32,
+) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![id];
+    while let Some(id) = pending.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Some(blockers) = blocked_by(id) {
+            if blockers.contains(&other) {
+                return true;
+            }
+            pending.extend(blockers);
+        }
+    }
+    return false;
+}
+
+/// `progress.store` -> `progress.store.<suffix>`, in the same directory
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ImportMode {
+    /// Keep existing tasks and add the imported ones next to them
+    Merge,
+    /// Drop every existing task first
+    Replace,
+}
+
+/// What checking a task set off besides the task itself
+#[derive(Debug, Default, PartialEq)]
+pub struct Checked {
+    /// Parents checked along with it, see `Store::auto_check_parents`
+    pub parents: Vec<u32>,
+    /// Tasks it is blocked by that are still open
+    pub open_blockers: Vec<u32>,
+}
+
+/// A change `Store::apply_to_tasks` can make to many tasks at once
+#[derive(Clone, Copy)]
+pub enum BulkAction {
+    Check,
+    Uncheck,
+    Remove,
+}
+
+#[derive(Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub removed: usize,
+    /// Already in the store, unchanged
+    pub skipped: usize,
+    /// Imported tasks whose id was taken, as (old id, new id)
+    pub renumbered: Vec<(u32, u32)>,
+}
+
 pub struct Store {
-    root: String,
+    repository: Box<dyn TaskRepository>,
+    path: PathBuf,
+    /// None while a long session waits on the user, see `unlock`
+    lock: Option<StoreLock>,
+    lock_timeout: Duration,
     pub metadata: Metadata,
+    /// Every task, whatever the scope
     pub tasks: Vec<Task>,
+    /// Only kept for large stores, see `INDEX_THRESHOLD`
+    search_index: Option<SearchIndex>,
+    /// Prefix of the project picked with `--project`
+    scope: Option<String>,
+    /// Check a task once all of its subtasks are done, see `AUTO_CHECK_PARENTS_ENV`
+    pub auto_check_parents: bool,
 }
 
 impl Store {
-    pub fn save(&self) {
-        let file_path = path::Path::new(&self.root).join(STORE_FILE);
-        let mut content_buffer = String::new();
+    /// Commits every change made since the store was opened (or last saved)
+    pub fn save(&mut self) -> Result<(), ProgressError> {
+        return self.repository.commit(&self.metadata);
+    }
 
-        // dump metadata
-        self.metadata.dump(&mut content_buffer);
-        self.tasks
-            .iter()
-            .for_each(|task| task.dump(&mut content_buffer));
+    /// Opens the store, holding `mode` lock on it until the store is dropped
+    pub fn open(path: &Path, mode: LockMode, timeout: Duration) -> Result<Self, ProgressError> {
+        let lock = StoreLock::acquire(path, mode, timeout)?;
+        let mut repository = open_repository(path, mode == LockMode::Exclusive)?;
+        let (metadata, tasks) = repository.load()?;
+
+        let mut store = Store {
+            repository,
+            path: path.to_path_buf(),
+            lock: Some(lock),
+            lock_timeout: timeout,
+            metadata,
+            tasks,
+            search_index: None,
+            scope: None,
+            auto_check_parents: std::env::var(AUTO_CHECK_PARENTS_ENV)
+                .is_ok_and(|value| matches!(value.trim(), "1" | "true")),
+        };
+        store.reindex();
+        return Ok(store);
+    }
+
+    /// Locks the store in `mode` and reads it again, so sessions that stay
+    /// open see what other processes saved meanwhile. Unsaved changes are
+    /// dropped.
+    pub fn relock(&mut self, mode: LockMode) -> Result<(), ProgressError> {
+        // our own shared lock would keep us from getting the exclusive one
+        self.lock = None;
+        self.lock = Some(StoreLock::acquire(&self.path, mode, self.lock_timeout)?);
+
+        self.repository = open_repository(&self.path, mode == LockMode::Exclusive)?;
+        let (metadata, tasks) = self.repository.load()?;
+        self.metadata = metadata;
+        self.tasks = tasks;
+        self.reindex();
+        return Ok(());
+    }
 
-        let mut file = fs::File::create(file_path).expect("Could not create store file");
-        file.write(content_buffer.as_bytes())
-            .expect("Could not write to store");
+    /// Lets go of the lock while the store is only kept around, like while
+    /// the shell, the TUI or the editor wait on the user. `locked` takes it
+    /// again.
+    pub fn unlock(&mut self) {
+        self.lock = None;
     }
 
-    pub fn open(root: &str) -> Result<Self, String> {
-        let file_path = path::Path::new(root).join(STORE_FILE);
+    /// Runs `command` on the store as it is now, holding a `mode` lock just
+    /// for as long as it takes
+    pub fn locked<T>(
+        &mut self,
+        mode: LockMode,
+        command: impl FnOnce(&mut Store) -> Result<T, ProgressError>,
+    ) -> Result<T, ProgressError> {
+        self.relock(mode)?;
+        let result = command(self);
+        self.unlock();
+        return result;
+    }
+
+    /// Limits listings to one project and puts new tasks in it. Without a
+    /// scope every project but the archived ones is shown.
+    pub fn set_scope(&mut self, project: Option<&str>) -> Result<(), ProgressError> {
+        self.scope = match project {
+            Some(project) => Some(self.resolve_project(project)?),
+            None => None,
+        };
+        return Ok(());
+    }
+
+    /// Whether `task` is shown in the current scope
+    pub fn is_visible(&self, task: &Task) -> bool {
+        match &self.scope {
+            Some(prefix) => return task.prefix() == prefix,
+            None => {
+                return !self
+                    .project(task.prefix())
+                    .is_some_and(|project| project.archived)
+            }
+        }
+    }
+
+    /// Tasks in the current scope
+    pub fn visible_tasks(&self) -> impl Iterator<Item = &Task> {
+        return self.tasks.iter().filter(|task| self.is_visible(task));
+    }
+
+    /// The project with `prefix`, none for the inbox
+    pub fn project(&self, prefix: &str) -> Option<&Project> {
+        return self
+            .metadata
+            .projects
+            .iter()
+            .find(|project| project.prefix == prefix);
+    }
+
+    /// Projects shown in the current scope, the inbox first as `None`
+    pub fn visible_projects(&self) -> Vec<Option<&Project>> {
+        let mut projects = vec![None];
+        projects.extend(self.metadata.projects.iter().map(Some));
+        return projects
+            .into_iter()
+            .filter(|project| {
+                let prefix = project.map_or(INBOX_PREFIX, |project| &project.prefix);
+                match &self.scope {
+                    Some(scope) => return scope == prefix,
+                    None => return !project.is_some_and(|project| project.archived),
+                }
+            })
+            .collect();
+    }
 
-        if !file_path.exists() {
-            return Ok(Store {
-                root: root.to_string(),
-                metadata: Metadata::default(),
-                tasks: vec![],
+    /// Finds a project by name or prefix, in any case, giving its prefix
+    pub fn resolve_project(&self, name: &str) -> Result<String, ProgressError> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case(INBOX) || name.eq_ignore_ascii_case(INBOX_PREFIX) {
+            return Ok(INBOX_PREFIX.to_string());
+        }
+        return self
+            .metadata
+            .projects
+            .iter()
+            .find(|project| {
+                project.name.eq_ignore_ascii_case(name) || project.prefix.eq_ignore_ascii_case(name)
+            })
+            .map(|project| project.prefix.clone())
+            .ok_or_else(|| {
+                ProgressError::Usage(format!(
+                    "There's no project \"{}\", see `progress project list`",
+                    name
+                ))
             });
+    }
+
+    /// Adds a project, its prefix made from the name unless one is given
+    pub fn create_project(
+        &mut self,
+        name: &str,
+        prefix: Option<String>,
+    ) -> Result<String, ProgressError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ProgressError::Usage("Need to include a name".to_string()));
+        }
+        if self.resolve_project(name).is_ok() {
+            return Err(ProgressError::Usage(format!(
+                "There's already a project called \"{}\"",
+                name
+            )));
+        }
+
+        let prefix = match prefix {
+            Some(prefix) => prefix,
+            None => {
+                let letters: String = name
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .take(4)
+                    .collect();
+                parse_prefix(&letters).map_err(|_| {
+                    ProgressError::Usage(format!(
+                        "Can't make a prefix out of \"{}\", pass one with --prefix",
+                        name
+                    ))
+                })?
+            }
+        };
+        if prefix == INBOX_PREFIX || self.project(&prefix).is_some() {
+            return Err(ProgressError::Usage(format!(
+                "The prefix {} is taken, pass another one with --prefix",
+                prefix
+            )));
         }
 
-        // open file
-        let mut file = fs::File::open(file_path).map_err(|e| e.to_string())?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf).map_err(|e| e.to_string())?;
+        self.metadata.projects.push(Project {
+            name: name.to_string(),
+            prefix: prefix.clone(),
+            archived: false,
+        });
+        return Ok(prefix);
+    }
 
-        // parse content
-        let lines = buf.split('\n').collect::<Vec<&str>>();
-        let mut metadata = Metadata::default();
-        let mut state: Option<ParseState> = None;
-        let mut tasks: Vec<Task> = vec![];
+    /// Renames a project, its prefix and task ids stay the same
+    pub fn rename_project(&mut self, project: &str, name: &str) -> Result<(), ProgressError> {
+        let prefix = self.resolve_project(project)?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ProgressError::Usage("Need to include a name".to_string()));
+        }
+        if self
+            .resolve_project(name)
+            .is_ok_and(|existing| existing != prefix)
+        {
+            return Err(ProgressError::Usage(format!(
+                "There's already a project called \"{}\"",
+                name
+            )));
+        }
 
-        for line in lines {
-            if line.len() == 0 {
-                continue;
+        let project = self.project_mut(&prefix)?;
+        project.name = name.to_string();
+        return Ok(());
+    }
+
+    /// Archives a project, or brings it back with `archived` false
+    pub fn archive_project(&mut self, project: &str, archived: bool) -> Result<(), ProgressError> {
+        let prefix = self.resolve_project(project)?;
+        let project = self.project_mut(&prefix)?;
+        project.archived = archived;
+        return Ok(());
+    }
+
+    /// Every project but the inbox, which can't be renamed or archived
+    fn project_mut(&mut self, prefix: &str) -> Result<&mut Project, ProgressError> {
+        return self
+            .metadata
+            .projects
+            .iter_mut()
+            .find(|project| project.prefix == prefix)
+            .ok_or_else(|| ProgressError::Usage("The inbox can't be changed".to_string()));
+    }
+
+    /// Moves a task and its subtasks to another project. Their numbers stay,
+    /// only the prefix changes.
+    pub fn move_task(&mut self, id: u32, project: &str) -> Result<(), ProgressError> {
+        let task = self
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .ok_or_else(|| not_found(id))?;
+        if let Some(parent) = task.parent {
+            return Err(ProgressError::Usage(format!(
+                "{} is a subtask of {}, move that instead",
+                task.key(),
+                self.task_key(parent)
+            )));
+        }
+
+        let prefix = self.resolve_project(project)?;
+        if self
+            .project(&prefix)
+            .is_some_and(|project| project.archived)
+        {
+            return Err(ProgressError::Usage(format!(
+                "Project {} is archived",
+                project
+            )));
+        }
+
+        let mut family = vec![id];
+        let mut index = 0;
+        while let Some(&id) = family.get(index) {
+            family.extend(self.subtasks(id).map(|task| task.id));
+            index += 1;
+        }
+
+        let project = Some(prefix).filter(|prefix| prefix != INBOX_PREFIX);
+        for task in &mut self.tasks {
+            if family.contains(&task.id) {
+                task.project = project.clone();
+                self.repository.update(task)?;
             }
+        }
+        return Ok(());
+    }
 
-            match line {
-                ":metadata" => {
-                    state = Some(ParseState::WritingMetadata(0));
-                }
-                ":task" => {
-                    state = Some(ParseState::WritingTask(0));
-                    let task = Task::default();
-                    tasks.push(task);
+    /// Marks the task as blocked by each of `blockers`, or no longer blocked
+    /// with `block` false. A task can't end up waiting on itself.
+    pub fn block_task(
+        &mut self,
+        id: u32,
+        blockers: &[u32],
+        block: bool,
+    ) -> Result<(), ProgressError> {
+        if !self.tasks.iter().any(|task| task.id == id) {
+            return Err(not_found(id));
+        }
+        if block {
+            for &blocker in blockers {
+                if !self.tasks.iter().any(|task| task.id == blocker) {
+                    return Err(not_found(blocker));
                 }
-                ":end" => {
-                    // ensure the pointers ended well
-                    if let Some(s) = state {
-                        match s {
-                            ParseState::WritingMetadata(n) => {
-                                if n != 1 {
-                                    return Err("Premature eol for metadata".to_string());
-                                }
-                            }
-                            ParseState::WritingTask(n) => {
-                                if n != 5 {
-                                    return Err("Premature eol for task".to_string());
-                                }
-                            }
-                        }
-                    }
-                    state = None;
+                if blocker == id {
+                    return Err(ProgressError::Usage(format!(
+                        "{} can't block itself",
+                        self.task_key(id)
+                    )));
                 }
-                _ => {
-                    let status = state.take();
-                    if let Some(mut status) = status {
-                        match status {
-                            ParseState::WritingMetadata(pointer) => match pointer {
-                                0 => {
-                                    metadata.last_task_id =
-                                        line.trim().parse::<u32>().map_err(|e| e.to_string())?;
-                                    status = ParseState::WritingMetadata(1);
-                                }
-                                _ => {
-                                    return Err("invalid metadata pointer".to_string());
-                                }
-                            },
-                            ParseState::WritingTask(pointer) => match pointer {
-                                0 => {
-                                    let task = tasks.pop();
-                                    if let Some(mut task) = task {
-                                        task.id = line
-                                            .trim()
-                                            .parse::<u32>()
-                                            .map_err(|e| e.to_string())?;
-                                        tasks.push(task);
-                                    } else {
-                                        unreachable!();
-                                    }
-                                    status = ParseState::WritingTask(1);
-                                }
-                                1 => {
-                                    let task = tasks.pop();
-                                    if let Some(mut task) = task {
-                                        task.done = match line.trim() {
-                                            "[x]" => true,
-                                            "[]" => false,
-                                            n => {
-                                                return Err(format!(
-                                                    "Invalid value {n} for task property"
-                                                ))
-                                            }
-                                        };
-                                        tasks.push(task);
-                                    } else {
-                                        unreachable!();
-                                    }
-                                    status = ParseState::WritingTask(2);
-                                }
-                                2 => {
-                                    let task = tasks.pop();
-                                    if let Some(mut task) = task {
-                                        task.label = line.trim().to_string();
-                                        tasks.push(task);
-                                    } else {
-                                        unreachable!();
-                                    }
-                                    status = ParseState::WritingTask(3);
-                                }
-                                3 => {
-                                    let task = tasks.pop();
-                                    if let Some(mut task) = task {
-                                        task.date_created = line
-                                            .trim()
-                                            .parse::<i64>()
-                                            .map_err(|e| e.to_string())?;
-                                        tasks.push(task);
-                                    } else {
-                                        unreachable!();
-                                    }
-                                    status = ParseState::WritingTask(4);
-                                }
-                                4 => {
-                                    let task = tasks.pop();
-                                    if let Some(mut task) = task {
-                                        task.date_checked = match line.trim() {
-                                            "-" => None,
-                                            n => Some(n.parse::<i64>().map_err(|e| e.to_string())?),
-                                        };
-                                        tasks.push(task);
-                                    } else {
-                                        unreachable!();
-                                    }
-                                    status = ParseState::WritingTask(5);
-                                }
-                                _ => {
-                                    return Err("invalid task pointer".to_string());
-                                }
-                            },
-                        }
-                        state = Some(status);
-                    }
+                if self.waits_on(blocker, id) {
+                    return Err(ProgressError::Usage(format!(
+                        "{} already waits on {}, blocking it would be a cycle",
+                        self.task_key(blocker),
+                        self.task_key(id)
+                    )));
                 }
             }
         }
 
-        return Ok(Store {
-            root: root.to_string(),
-            metadata,
-            tasks,
+        let task = self
+            .tasks
+            .iter_mut()
+            .find(|task| task.id == id)
+            .ok_or_else(|| not_found(id))?;
+        for blocker in blockers {
+            if block {
+                task.blocked_by.insert(*blocker);
+            } else {
+                task.blocked_by.remove(blocker);
+            }
+        }
+        return self.repository.update(task);
+    }
+
+    /// Whether `id` is blocked by `other`, directly or through other blockers
+    pub fn waits_on(&self, id: u32, other: u32) -> bool {
+        return waits_on(
+            |id| {
+                self.tasks
+                    .iter()
+                    .find(|task| task.id == id)
+                    .map(|task| &task.blocked_by)
+            },
+            id,
+            other,
+        );
+    }
+
+    /// "was still blocked by ..." for a task that was checked before the
+    /// tasks it waits on
+    pub fn blocked_warning(&self, checked: &Checked) -> Option<String> {
+        if checked.open_blockers.is_empty() {
+            return None;
+        }
+        return Some(format!(
+            "was still blocked by {}",
+            self.task_keys(&checked.open_blockers)
+        ));
+    }
+
+    /// Tasks the task is blocked by that aren't done yet, in id order
+    pub fn open_blockers<'a>(&'a self, task: &'a Task) -> impl Iterator<Item = &'a Task> {
+        return task.blocked_by.iter().filter_map(|id| {
+            self.tasks
+                .iter()
+                .find(|blocker| blocker.id == *id && !blocker.done)
+        });
+    }
+
+    /// Direct subtasks of the task, in id order
+    pub fn subtasks(&self, id: u32) -> impl Iterator<Item = &Task> {
+        return self
+            .tasks
+            .iter()
+            .filter(move |task| task.parent == Some(id));
+    }
+
+    /// The number of the task a typed id names. The prefix has to be the
+    /// task's own, so a mistyped project doesn't land on another task.
+    pub fn resolve_task(&self, id: &TaskId) -> Result<u32, ProgressError> {
+        return self
+            .tasks
+            .iter()
+            .find(|task| id.matches(task))
+            .map(|task| task.id)
+            .ok_or_else(|| ProgressError::NotFound(id.to_string()));
+    }
+
+    /// Keys of the tasks separated by commas, like TSK-3, WORK-4
+    pub fn task_keys(&self, ids: &[u32]) -> String {
+        return ids
+            .iter()
+            .map(|id| self.task_key(*id))
+            .collect::<Vec<String>>()
+            .join(", ");
+    }
+
+    /// TSK-3 or WORK-3, for a task that may have been removed since
+    pub fn task_key(&self, id: u32) -> String {
+        return self
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .map(|task| task.key())
+            .unwrap_or_else(|| format!("{}-{}", INBOX_PREFIX, id));
+    }
+
+    /// Builds the search index once the store is large enough to need one
+    fn reindex(&mut self) {
+        self.search_index = if self.tasks.len() >= INDEX_THRESHOLD {
+            Some(SearchIndex::build(&self.tasks))
+        } else {
+            None
+        };
+    }
+
+    /// Tasks in scope whose label matches `terms`, best match first
+    pub fn search(&self, terms: &str) -> Vec<SearchHit> {
+        let mut hits = search::search(&self.tasks, self.search_index.as_ref(), terms);
+        hits.retain(|hit| self.is_visible(&hit.task));
+        return hits;
+    }
+
+    /// Tasks in scope matching `query`
+    pub fn query(&self, query: &TaskQuery) -> Result<Vec<Task>, ProgressError> {
+        let mut tasks = self.repository.query(query)?;
+        tasks.retain(|task| self.is_visible(task));
+        return Ok(tasks);
+    }
+
+    /// Tasks created today
+    pub fn tasks_today(&self) -> Result<Vec<Task>, ProgressError> {
+        let today_start = today_start();
+        return self.query(&TaskQuery {
+            created_from: Some(today_start),
+            created_until: Some(today_start + 24 * 60 * 60),
+            ..TaskQuery::default()
         });
     }
 
-    pub fn add_task(&mut self, task: Task) {
+    /// Unchecked tasks created before today
+    pub fn carry_over_tasks(&self) -> Result<Vec<Task>, ProgressError> {
+        let tasks = self.query(&TaskQuery {
+            created_until: Some(today_start()),
+            ..TaskQuery::default()
+        })?;
+        return Ok(tasks
+            .into_iter()
+            .filter(|task| task.date_checked.is_none())
+            .collect());
+    }
+
+    /// Adds `task` as a new open task created now, returning the id it got.
+    /// It goes in the project in scope, the inbox without one, and subtasks
+    /// in their parent's project. Like other changes it is kept once the
+    /// store is saved.
+    pub fn create_task(&mut self, task: Task) -> Result<u32, ProgressError> {
+        let mut project = self.scope.clone().filter(|prefix| prefix != INBOX_PREFIX);
+        if let Some(parent_id) = task.parent {
+            let parent = self
+                .tasks
+                .iter()
+                .find(|parent| parent.id == parent_id)
+                .ok_or_else(|| not_found(parent_id))?;
+            if parent.done {
+                return Err(ProgressError::TaskFinished(parent.key()));
+            }
+            project = parent.project.clone();
+        }
+
+        if let Some(project) = project.as_deref().and_then(|prefix| self.project(prefix)) {
+            if project.archived {
+                return Err(ProgressError::Usage(format!(
+                    "Project {} is archived, unarchive it to add tasks",
+                    project.name
+                )));
+            }
+        }
+
+        let id = self.metadata.last_task_id;
+        let task = Task {
+            id,
+            done: false,
+            date_checked: None,
+            date_created: Local::now().timestamp(),
+            project,
+            ..task
+        };
+
+        self.add_task(task)?;
+        return Ok(id);
+    }
+
+    fn add_task(&mut self, task: Task) -> Result<(), ProgressError> {
+        self.repository.insert(&task)?;
+        if let Some(index) = &mut self.search_index {
+            index.insert(&task);
+        }
         self.tasks.push(task);
         self.metadata.last_task_id += 1;
-        self.save();
+        return Ok(());
     }
 
-    pub fn show_task_information(&self, id: u32) {
-        for task in &self.tasks {
-            if task.id == id {
-                println!(
-                    "{} · TSK-{}",
-                    if task.done {
-                        "[x]".green()
-                    } else {
-                        "[-]".red()
-                    },
-                    task.id
-                );
-                println!("----------------------");
-                println!("{}", task.label);
-                println!("Created ({})", format_timestamp_ago(task.date_created));
-
-                if let Some(date_checked) = task.date_checked {
-                    println!("Finished ({})", format_timestamp_ago(date_checked))
+    /// Brings exported tasks into the store. Merging skips tasks that are
+    /// already here unchanged (under any id) and gives the ones whose id is
+    /// taken a fresh id past `last_task_id`, so ids handed out later can't
+    /// collide either. With a scope, replacing only removes the tasks in it
+    /// and every imported task goes into it.
+    pub fn import_tasks(
+        &mut self,
+        metadata: Option<Metadata>,
+        tasks: Vec<Task>,
+        mode: ImportMode,
+    ) -> Result<ImportSummary, ProgressError> {
+        let mut summary = ImportSummary::default();
+
+        if let ImportMode::Replace = mode {
+            let (removed, kept) = std::mem::take(&mut self.tasks)
+                .into_iter()
+                .partition(|task| self.is_visible(task));
+            self.tasks = kept;
+            for task in removed {
+                self.repository.delete(&task)?;
+                summary.removed += 1;
+            }
+        }
+        let project = self.scope.clone().filter(|prefix| prefix != INBOX_PREFIX);
+        if let Some(metadata) = &metadata {
+            for project in &metadata.projects {
+                if self.project(&project.prefix).is_none() && project.prefix != INBOX_PREFIX {
+                    self.metadata.projects.push(project.clone());
+                }
+            }
+        }
+
+        let highest_id = self
+            .tasks
+            .iter()
+            .chain(tasks.iter())
+            .map(|task| task.id)
+            .max();
+        let mut next_id = highest_id
+            .map(|id| id + 1)
+            .unwrap_or(0)
+            .max(self.metadata.last_task_id)
+            .max(
+                metadata
+                    .as_ref()
+                    .map(|metadata| metadata.last_task_id)
+                    .unwrap_or(0),
+            );
+
+        // where each imported id ended up, so subtasks keep their parent and
+        // blocked tasks their blockers
+        let mut new_ids: HashMap<u32, u32> = HashMap::new();
+        let mut imported: Vec<Task> = vec![];
+        for mut task in tasks {
+            if self.scope.is_some() {
+                task.project = project.clone();
+            }
+            // the same task under any id, e.g. one renumbered by an earlier import
+            let same = self.tasks.iter().chain(imported.iter()).find(|existing| {
+                **existing
+                    == Task {
+                        id: existing.id,
+                        ..task.clone()
+                    }
+            });
+            if let Some(existing) = same {
+                new_ids.insert(task.id, existing.id);
+                summary.skipped += 1;
+                continue;
+            }
+
+            let old_id = task.id;
+            if self
+                .tasks
+                .iter()
+                .chain(imported.iter())
+                .any(|existing| existing.id == task.id)
+            {
+                summary.renumbered.push((task.id, next_id));
+                task.id = next_id;
+                next_id += 1;
+            }
+            new_ids.insert(old_id, task.id);
+            imported.push(task);
+        }
+
+        for mut task in imported {
+            // CSV exports name projects only by the prefixes of their tasks
+            if let Some(prefix) = &task.project {
+                if self.project(prefix).is_none() && prefix != INBOX_PREFIX {
+                    self.metadata.projects.push(Project {
+                        name: prefix.to_lowercase(),
+                        prefix: prefix.clone(),
+                        archived: false,
+                    });
                 }
-                return;
             }
+            task.parent = task
+                .parent
+                .map(|parent| new_ids.get(&parent).copied().unwrap_or(parent));
+            task.blocked_by = task
+                .blocked_by
+                .iter()
+                .map(|id| new_ids.get(id).copied().unwrap_or(*id))
+                .collect();
+            self.repository.insert(&task)?;
+            self.tasks.push(task);
+            summary.added += 1;
         }
 
-        let error_message = format!("Task {} does not exist", id);
-        println!("{}", error_message.red());
+        self.metadata.last_task_id = next_id;
+        self.reindex();
+        return Ok(summary);
     }
 
-    pub fn remove_task(&mut self, id: u32) -> Result<(), &str> {
+    pub fn remove_task(&mut self, id: u32) -> Result<(), ProgressError> {
+        if self.subtasks(id).next().is_some() {
+            return Err(ProgressError::Usage(format!(
+                "{} has subtasks, remove them first",
+                self.task_key(id)
+            )));
+        }
         for (index, task) in self.tasks.iter().enumerate() {
             if task.id != id {
                 continue;
             }
             // we cant remove a task if it wasnt added today
             let today = Local::now().date_naive();
-            let task_created_date = DateTime::from_timestamp(task.date_created, 0).unwrap();
+            let created_today = DateTime::from_timestamp(task.date_created, 0)
+                .is_some_and(|date| date.date_naive() == today);
 
-            if task_created_date.date_naive() == today {
-                self.tasks.swap_remove(index);
+            if created_today {
+                self.repository.delete(task)?;
+                let task = self.tasks.swap_remove(index);
+                if let Some(search_index) = &mut self.search_index {
+                    search_index.remove(&task);
+                }
+                // tasks it blocked no longer wait on it
+                for task in &mut self.tasks {
+                    if task.blocked_by.remove(&id) {
+                        self.repository.update(task)?;
+                    }
+                }
             } else {
-                return Err("Cannot remove task that wasn't added today");
+                return Err(ProgressError::EditWindowClosed(
+                    "Cannot remove task that wasn't added today".to_string(),
+                ));
             }
-            break;
+            return Ok(());
         }
-        return Ok(());
+        return Err(not_found(id));
     }
 
-    pub fn relabel_task(&mut self, id: u32, label: &str) -> Result<(), &str> {
+    pub fn relabel_task(&mut self, id: u32, label: &str) -> Result<(), ProgressError> {
         for task in &mut self.tasks {
             if task.id != id {
                 continue;
             }
 
             if task.done {
-                println!("{}", "Cannot rename finished task".red());
-                return Ok(()); // bad design here, but who cares
+                return Err(ProgressError::TaskFinished(task.key()));
             }
 
-            task.label = label.to_owned();
-            return Ok(());
+            if let Some(index) = &mut self.search_index {
+                index.remove(task);
+                task.label = label.to_owned();
+                index.insert(task);
+            } else {
+                task.label = label.to_owned();
+            }
+            return self.repository.update(task);
+        }
+        return Err(not_found(id));
+    }
+
+    pub fn prioritize_task(&mut self, id: u32, priority: Priority) -> Result<(), ProgressError> {
+        for task in &mut self.tasks {
+            if task.id != id {
+                continue;
+            }
+
+            if task.done {
+                return Err(ProgressError::TaskFinished(task.key()));
+            }
+
+            task.priority = priority;
+            return self.repository.update(task);
+        }
+        return Err(not_found(id));
+    }
+
+    /// Sets or clears when the task is due
+    pub fn set_due_date(&mut self, id: u32, date_due: Option<i64>) -> Result<(), ProgressError> {
+        for task in &mut self.tasks {
+            if task.id != id {
+                continue;
+            }
+
+            if task.done {
+                return Err(ProgressError::TaskFinished(task.key()));
+            }
+
+            task.date_due = date_due;
+            return self.repository.update(task);
         }
-        println!("No task with te specified id");
+        return Err(not_found(id));
+    }
+
+    /// Adds `tags` to the task, or takes them off with `add` false. Finished
+    /// tasks can still be tagged, tags only sort tasks into groups.
+    pub fn tag_task(&mut self, id: u32, tags: &[String], add: bool) -> Result<(), ProgressError> {
+        for task in &mut self.tasks {
+            if task.id != id {
+                continue;
+            }
+
+            for tag in tags {
+                if add {
+                    task.tags.insert(tag.clone());
+                } else {
+                    task.tags.remove(tag);
+                }
+            }
+            return self.repository.update(task);
+        }
+        return Err(not_found(id));
+    }
+
+    /// Puts the tasks back the way they were in `snapshot`, a copy of
+    /// `tasks` taken earlier, in memory and in the repository alike. Unsaved
+    /// changes since are undone, so a later save doesn't write them.
+    pub fn restore(&mut self, snapshot: Vec<Task>) -> Result<(), ProgressError> {
+        for task in &self.tasks {
+            if !snapshot.iter().any(|old| old.id == task.id) {
+                self.repository.delete(task)?;
+            }
+        }
+        for old in &snapshot {
+            match self.tasks.iter().find(|task| task.id == old.id) {
+                Some(task) if task == old => {}
+                Some(_) => self.repository.update(old)?,
+                None => self.repository.insert(old)?,
+            }
+        }
+        self.tasks = snapshot;
+        self.reindex();
         return Ok(());
     }
 
-    pub fn toggle_check_task(&mut self, id: u32, check: bool) -> Result<(), &str> {
+    /// Applies `action` to each task in turn, carrying on past the ones that
+    /// fail. Nothing is saved, so the caller decides whether the tasks that
+    /// succeeded are kept, see `restore`.
+    pub fn apply_to_tasks(
+        &mut self,
+        ids: &[u32],
+        action: BulkAction,
+    ) -> Vec<(u32, Result<Checked, ProgressError>)> {
+        return ids
+            .iter()
+            .map(|&id| {
+                let result = match action {
+                    BulkAction::Check => self.toggle_check_task(id, true),
+                    BulkAction::Uncheck => self.toggle_check_task(id, false),
+                    BulkAction::Remove => self.remove_task(id).map(|_| Checked::default()),
+                };
+                (id, result)
+            })
+            .collect();
+    }
+
+    /// Checks or unchecks the task. With `auto_check_parents`, checking the
+    /// last open subtask checks its parent too. Checking a task that is still
+    /// blocked goes ahead, the open blockers are returned to warn about.
+    pub fn toggle_check_task(&mut self, id: u32, check: bool) -> Result<Checked, ProgressError> {
+        let open_blockers = match self.tasks.iter().find(|task| task.id == id) {
+            Some(task) if check => self.open_blockers(task).map(|task| task.id).collect(),
+            _ => vec![],
+        };
         for task in self.tasks.iter_mut() {
             if task.id != id {
                 continue;
@@ -319,9 +1209,9 @@ impl Store {
 
             if task.done == check {
                 return Err(if check {
-                    "Task already done"
+                    ProgressError::AlreadyDone(task.key())
                 } else {
-                    "Task not completed yet"
+                    ProgressError::NotDone(task.key())
                 });
             }
 
@@ -333,186 +1223,434 @@ impl Store {
             } else {
                 // we cant uncheck task if not the same day
                 let today = Local::now().date_naive();
-                let task_created_date = DateTime::from_timestamp(task.date_created, 0).unwrap();
+                let created_today = DateTime::from_timestamp(task.date_created, 0)
+                    .is_some_and(|date| date.date_naive() == today);
 
-                if task_created_date.date_naive() == today {
+                if created_today {
                     task.done = false;
                     task.date_checked = None;
                 } else {
-                    return Err("Cannot uncheck task that wasn't checked today");
+                    return Err(ProgressError::EditWindowClosed(
+                        "Cannot uncheck task that wasn't checked today".to_string(),
+                    ));
                 }
             }
-            break;
+            self.repository.update(task)?;
+
+            let parent = task.parent;
+            let parents = if check && self.auto_check_parents {
+                self.check_finished_parents(parent)?
+            } else {
+                vec![]
+            };
+            return Ok(Checked {
+                parents,
+                open_blockers,
+            });
         }
-        return Ok(());
+        return Err(not_found(id));
     }
 
-    pub fn show_info(&self) {
-        let now = Local::now();
-        let today = now.date_naive();
+    /// Checks `parent` if all of its subtasks are done, then its own parent
+    /// and so on up
+    fn check_finished_parents(&mut self, parent: Option<u32>) -> Result<Vec<u32>, ProgressError> {
+        let now = Local::now().timestamp();
+        let mut checked = vec![];
+        let mut parent = parent;
 
-        let tasks_today: Vec<&Task> = self
-            .tasks
-            .iter()
-            .filter(|task| {
-                let task_date = DateTime::from_timestamp(task.date_created, 0)
-                    .map(|dt| dt.date_naive())
-                    .unwrap_or(today);
-                task_date == today
-            })
-            .collect();
+        while let Some(id) = parent {
+            let finished = self.subtasks(id).all(|task| task.done);
+            let task = match self.tasks.iter_mut().find(|task| task.id == id) {
+                Some(task) if finished && !task.done => task,
+                _ => break,
+            };
+            task.done = true;
+            task.date_checked = Some(now);
+            self.repository.update(task)?;
+            checked.push(id);
+            parent = task.parent;
+        }
+        return Ok(checked);
+    }
+}
 
-        let unchecked_tasks_before_today: Vec<&Task> = self
-            .tasks
-            .iter()
-            .filter(|task| {
-                let task_date = DateTime::from_timestamp(task.date_created, 0)
-                    .map(|dt| dt.date_naive())
-                    .unwrap_or(today);
-                task_date < today && task.date_checked.is_none()
-            })
-            .collect();
+/// The error for a task number nothing has, keyed like an inbox task
+pub fn not_found(id: u32) -> ProgressError {
+    return ProgressError::NotFound(format!("{}-{}", INBOX_PREFIX, id));
+}
 
-        if tasks_today.is_empty() {
-            // there's no task created today
-            if unchecked_tasks_before_today.is_empty() {
-                // there's o tsk created in the past that needs to be do today
-                println!("{}", "No tasks for today".green().bold());
-            }
-        } else {
-            println!("{}", "Tasks for Today:".green().bold());
-            for task in &tasks_today {
-                println!(
-                    "{} {}",
-                    if task.done {
-                        format!("TSK-{} - [x]", task.id,).green()
-                    } else {
-                        format!("TSK-{} - [-]", task.id,).red()
-                    },
-                    if task.done {
-                        task.label.strikethrough()
-                    } else {
-                        task.label.normal()
+/// Task ids separated by whitespace, none if any of them isn't a number
+pub fn split_ids(text: &str) -> Option<BTreeSet<u32>> {
+    return text
+        .split_whitespace()
+        .map(|id| id.parse::<u32>().ok())
+        .collect();
+}
+
+/// Start of the current day. Task dates are compared by their UTC day.
+fn today_start() -> i64 {
+    let today = Local::now().date_naive();
+    return today.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::migrations;
+    use crate::text_repository::TextRepository;
+    use crate::utils::parse_task_id;
+    use proptest::{
+        collection::{btree_set, vec},
+        option,
+        prelude::*,
+    };
+    use std::{
+        fs, process,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static STORE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// A store file in a directory of its own, removed again on drop so a
+    /// failing assertion doesn't leave it behind. Backups and the like go
+    /// in the same directory, so they go with it.
+    pub(crate) struct TempStore {
+        pub(crate) path: PathBuf,
+    }
+
+    impl TempStore {
+        fn new() -> Self {
+            return TempStore::named(STORE_FILE);
+        }
+
+        pub(crate) fn named(file_name: &str) -> Self {
+            let n = STORE_COUNTER.fetch_add(1, Ordering::SeqCst);
+            let directory =
+                std::env::temp_dir().join(format!("progress-test-{}-{}", process::id(), n));
+            fs::create_dir_all(&directory).unwrap();
+            return TempStore {
+                path: directory.join(file_name),
+            };
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.path.parent().unwrap());
+        }
+    }
+
+    fn open(path: &Path) -> Store {
+        Store::open(path, LockMode::Exclusive, Duration::ZERO).unwrap()
+    }
+
+    fn task(label: &str) -> Task {
+        return Task {
+            label: label.to_string(),
+            ..Task::default()
+        };
+    }
+
+    /// A new store with an open inbox task for each label, numbered from 0
+    fn store_with(labels: &[&str]) -> (TempStore, Store) {
+        let temp = TempStore::new();
+        let mut store = open(&temp.path);
+        for label in labels {
+            store.create_task(task(label)).unwrap();
+        }
+        return (temp, store);
+    }
+
+    fn task_strategy() -> impl Strategy<Value = Task> {
+        (
+            any::<u32>(),
+            any::<bool>(),
+            any::<String>(),
+            any::<i64>(),
+            option::of(any::<i64>()),
+            prop_oneof![
+                Just(Priority::High),
+                Just(Priority::Medium),
+                Just(Priority::Low),
+                Just(Priority::None),
+            ],
+            option::of(any::<i64>()),
+            btree_set("[a-z0-9][a-z0-9_/-]{0,8}", 0..4),
+            option::of("[A-Z][A-Z0-9]{0,7}"),
+            option::of(any::<u32>()),
+            btree_set(any::<u32>(), 0..4),
+        )
+            .prop_map(
+                |(
+                    id,
+                    done,
+                    label,
+                    date_created,
+                    date_checked,
+                    priority,
+                    date_due,
+                    tags,
+                    project,
+                    parent,
+                    blocked_by,
+                )| {
+                    Task {
+                        id,
+                        done,
+                        label,
+                        date_created,
+                        date_checked,
+                        priority,
+                        date_due,
+                        tags,
+                        project,
+                        parent,
+                        blocked_by,
                     }
-                );
-            }
-            println!();
+                },
+            )
+    }
+
+    fn metadata_strategy() -> impl Strategy<Value = Metadata> {
+        let project = (any::<String>(), "[A-Z][A-Z0-9]{0,7}", any::<bool>()).prop_map(
+            |(name, prefix, archived)| Project {
+                name,
+                prefix,
+                archived,
+            },
+        );
+        return (any::<u32>(), vec(project, 0..4)).prop_map(|(last_task_id, projects)| Metadata {
+            last_task_id,
+            projects,
+        });
+    }
+
+    fn round_trip(metadata: &Metadata, tasks: &[Task]) -> (Metadata, Vec<Task>) {
+        let content = TextRepository::render(metadata, tasks);
+        let (version, body, first_line) = migrations::split_header(&content).unwrap();
+        assert_eq!(version, STORE_VERSION);
+        let (body, line_numbers) = migrations::migrate(body, version, first_line).unwrap();
+        TextRepository::parse(&body, &line_numbers).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn render_then_parse_round_trips(
+            metadata in metadata_strategy(),
+            tasks in vec(task_strategy(), 0..16),
+        ) {
+            let (parsed_metadata, parsed_tasks) = round_trip(&metadata, &tasks);
+            prop_assert_eq!(parsed_metadata, metadata);
+            prop_assert_eq!(parsed_tasks, tasks);
         }
+    }
 
-        if !unchecked_tasks_before_today.is_empty() {
-            println!("{}", "Carry-over tasks:".yellow().bold());
-            for task in &unchecked_tasks_before_today {
-                let leading = format!("TSK-{} ({})", task.id, format_timestamp_ago(task.date_created));
-                println!(
-                    "{} - [{}] {}",
-                    leading.on_truecolor(100, 100, 100),
-                    if task.done { "x" } else { " " },
-                    task.label
-                );
-            }
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn save_then_open_round_trips(
+            metadata in metadata_strategy(),
+            tasks in vec(task_strategy(), 0..16),
+        ) {
+            let temp = TempStore::new();
+            TextRepository::with_tasks(&temp.path, tasks.clone())
+                .commit(&metadata)
+                .unwrap();
+
+            let reopened = open(&temp.path);
+            prop_assert_eq!(reopened.metadata.clone(), metadata);
+            prop_assert_eq!(reopened.tasks.clone(), tasks);
         }
+    }
 
-        let total_tasks = self.tasks.len();
-        let completed_tasks = self.tasks.iter().filter(|task| task.done).count();
-        let incomplete_tasks = total_tasks - completed_tasks;
-        let done_today = self
-            .tasks
+    #[test]
+    fn labels_that_look_like_markers_round_trip() {
+        let labels = [
+            ":end",
+            ":task",
+            ":metadata",
+            ":version 1",
+            "",
+            " padded ",
+            "a\n:end\nb",
+            "\"\\n\"",
+        ];
+        let tasks = labels
             .iter()
-            .filter(|task| {
-                if let Some(checked_time) = task.date_checked {
-                    let checked_date = DateTime::from_timestamp(checked_time, 0)
-                        .map(|dt| dt.date_naive())
-                        .unwrap_or(today);
-                    return checked_date == today;
-                }
-                false
+            .enumerate()
+            .map(|(id, label)| Task {
+                id: id as u32,
+                label: label.to_string(),
+                ..Task::default()
             })
-            .count();
-        let done_before_today = completed_tasks - done_today;
+            .collect::<Vec<Task>>();
+        let metadata = Metadata {
+            last_task_id: tasks.len() as u32,
+            ..Metadata::default()
+        };
 
-        let earliest_date = self
-            .tasks
-            .iter()
-            .filter_map(|task| {
-                DateTime::from_timestamp(task.date_created, 0).map(|dt| dt.date_naive())
-            })
-            .min();
-        let latest_date = self
-            .tasks
-            .iter()
-            .filter_map(|task| {
-                DateTime::from_timestamp(task.date_created, 0).map(|dt| dt.date_naive())
-            })
-            .max();
+        assert_eq!(round_trip(&metadata, &tasks), (metadata, tasks));
+    }
+
+    #[test]
+    fn projects_scope_tasks() {
+        let (_temp, mut store) = store_with(&["in the inbox"]);
 
-        println!("{}", "\nStatistics:".green().bold());
-        println!("- Total tasks: {}", total_tasks);
-        println!("- Completed tasks: {}", completed_tasks);
-        println!("- Incomplete tasks: {}", incomplete_tasks);
-        println!("- Tasks created today: {}", tasks_today.len());
-        println!("- Tasks marked as done today: {}", done_today);
-        println!("- Tasks marked as done before today: {}", done_before_today);
-        println!(
-            "- Unchecked tasks from before today: {}",
-            unchecked_tasks_before_today.len()
+        assert_eq!(store.create_project("Side gig", None).unwrap(), "SIDE");
+        store.set_scope(Some("side gig")).unwrap();
+        let id = store.create_task(task("in the project")).unwrap();
+        assert_eq!(store.task_key(id), "SIDE-1");
+        // an id only finds the task under its own project's prefix
+        assert_eq!(
+            store
+                .resolve_task(&parse_task_id("side-1").unwrap())
+                .unwrap(),
+            id
         );
+        assert!(store
+            .resolve_task(&parse_task_id("TSK-1").unwrap())
+            .is_err());
+        assert_eq!(store.visible_tasks().count(), 1);
 
-        if let Some(earliest) = earliest_date {
-            println!("- Earliest task creation date: {}", earliest);
-        }
-        if let Some(latest) = latest_date {
-            println!("- Latest task creation date: {}", latest);
-        }
+        // names and prefixes are both taken
+        assert!(store.create_project("SIDE GIG", None).is_err());
+        assert!(store.create_project("Sidecar", None).is_err());
+
+        store.set_scope(None).unwrap();
+        assert_eq!(store.visible_tasks().count(), 2);
+        store.archive_project("side", true).unwrap();
+        assert_eq!(store.visible_tasks().count(), 1);
+        store.set_scope(Some("side")).unwrap();
+        assert!(store.create_task(task("archived")).is_err());
+    }
+
+    #[test]
+    fn subtasks_follow_their_parent() {
+        let (_temp, mut store) = store_with(&[]);
+        let with_parent = |label: &str, parent: Option<u32>| Task {
+            parent,
+            ..task(label)
+        };
+
+        store
+            .create_project("Release", Some("REL".to_string()))
+            .unwrap();
+        store.set_scope(Some("rel")).unwrap();
+        let parent = store.create_task(task("ship release")).unwrap();
+        store.set_scope(None).unwrap();
+        let first = store
+            .create_task(with_parent("tag build", Some(parent)))
+            .unwrap();
+        let second = store
+            .create_task(with_parent("publish", Some(parent)))
+            .unwrap();
+        assert_eq!(store.task_key(first), "REL-1");
+        assert_eq!(store.subtasks(parent).count(), 2);
+        assert!(store.create_task(with_parent("orphan", Some(42))).is_err());
 
-        println!("Use --help to see more.")
+        // a parent outlives its subtasks and moves with them
+        assert!(store.remove_task(parent).is_err());
+        assert!(store.move_task(first, "inbox").is_err());
+
+        store.auto_check_parents = true;
+        assert!(store
+            .toggle_check_task(first, true)
+            .unwrap()
+            .parents
+            .is_empty());
+        assert_eq!(
+            store.toggle_check_task(second, true).unwrap().parents,
+            vec![parent]
+        );
+        assert!(store.tasks.iter().all(|task| task.done));
     }
 
-    pub fn show_info_basic(&self) {
-        let today = Local::now().date_naive();
+    #[test]
+    fn blocked_tasks_warn_when_checked() {
+        let (_temp, mut store) = store_with(&["design", "build", "ship"]);
+        let (design, build, ship) = (0, 1, 2);
+        store.block_task(build, &[design], true).unwrap();
+        store.block_task(ship, &[build], true).unwrap();
+
+        // waiting on each other, directly or not, is refused
+        assert!(store.block_task(design, &[ship], true).is_err());
+        assert!(store.block_task(design, &[design], true).is_err());
+        assert!(store.block_task(design, &[42], true).is_err());
+
+        let checked = store.toggle_check_task(build, true).unwrap();
+        assert_eq!(checked.open_blockers, vec![design]);
+        assert!(store
+            .toggle_check_task(design, true)
+            .unwrap()
+            .open_blockers
+            .is_empty());
+
+        // removing a blocker frees the tasks waiting on it
+        store.remove_task(build).unwrap();
+        assert!(store.tasks.iter().all(|task| task.blocked_by.is_empty()));
+    }
 
-        // pending tasks (including unchecked tasks from previous days)
-        let pending_tasks_today: Vec<&Task> = self
+    #[test]
+    fn bulk_changes_carry_on_past_failures() {
+        let (temp, mut store) = store_with(&["open", "done", "parent"]);
+        let (open_task, done, parent) = (0, 1, 2);
+        let subtask = store
+            .create_task(Task {
+                parent: Some(parent),
+                ..task("subtask")
+            })
+            .unwrap();
+        store.toggle_check_task(done, true).unwrap();
+        store.save().unwrap();
+
+        let results = store.apply_to_tasks(&[done, open_task, 42], BulkAction::Check);
+        let ids: Vec<u32> = results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [done, open_task, 42]);
+        assert!(matches!(&results[0].1, Err(ProgressError::AlreadyDone(key)) if key == "TSK-1"));
+        assert_eq!(results[1].1.as_ref().unwrap(), &Checked::default());
+        assert!(matches!(&results[2].1, Err(ProgressError::NotFound(key)) if key == "TSK-42"));
+        assert!(store
             .tasks
             .iter()
-            .filter(|task| {
-                let task_date = DateTime::from_timestamp(task.date_created, 0)
-                    .map(|dt| dt.date_naive())
-                    .unwrap_or(today);
+            .any(|task| task.id == open_task && task.done));
 
-                // task is either from today or unchecked (not marked as done)
-                (task_date == today || task.date_checked.is_none()) && !task.done
-            })
-            .collect();
+        // the parent still has its subtask when its turn comes
+        let results = store.apply_to_tasks(&[parent, subtask], BulkAction::Remove);
+        assert!(results[0].1.is_err());
+        assert!(results[1].1.is_ok());
+        assert_eq!(store.tasks.len(), 3);
 
-        // unchecked tasks from previous days
-        let pending_tasks_previous_days: Vec<&Task> = self
+        // nothing is saved until the caller says so
+        drop(store);
+        let store = open(&temp.path);
+        assert_eq!(store.tasks.len(), 4);
+        assert!(!store
             .tasks
             .iter()
-            .filter(|task| {
-                let task_date = DateTime::from_timestamp(task.date_created, 0)
-                    .map(|dt| dt.date_naive())
-                    .unwrap_or(today);
+            .any(|task| task.id == open_task && task.done));
+    }
 
-                // task is from a previous day and unchecked
-                task_date < today && task.date_checked.is_none() && !task.done
-            })
-            .collect();
+    #[test]
+    fn opens_unversioned_store() {
+        let temp = TempStore::new();
+        let path = &temp.path;
+        fs::write(
+            path,
+            ":metadata\n2\n:end\n:task\n0\n[x]\n  buy milk  \n100\n200\n:end\n:task\n1\n[]\nsay \"hi\"\n300\n-\n:end\n",
+        )
+        .unwrap();
 
-        let total_pending = pending_tasks_today.len();
-        let from_previous_days = pending_tasks_previous_days.len();
+        let store = open(path);
+        assert_eq!(store.metadata.last_task_id, 2);
+        assert_eq!(store.tasks[0].label, "buy milk");
+        assert_eq!(store.tasks[0].date_checked, Some(200));
+        assert_eq!(store.tasks[1].label, "say \"hi\"");
+        drop(store);
 
-        if total_pending + from_previous_days == 0 {
-            println!(
-                "📅 {} You gotta lockin! create a task! see --help",
-                format!("[{}]", Local::now().format("%H:%M")).green(),
-            );
-        } else {
-            println!(
-                "📅 {} You have {} pending task(s) for today, {} from previous days",
-                format!("[{}]", Local::now().format("%H:%M")).green(),
-                total_pending.to_string().yellow().bold(),
-                from_previous_days.to_string().red().bold()
-            )
-        };
+        // opening for writing upgrades the file and keeps the original
+        assert!(sibling_path(path, "v1.bak").exists());
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.starts_with(&format!("{} {}\n", VERSION_HEADER, STORE_VERSION)));
     }
 }
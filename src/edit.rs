@@ -0,0 +1,408 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use colored::Colorize;
+
+use crate::ds::{Store, Task};
+use crate::error::ProgressError;
+use crate::lock::LockMode;
+use crate::utils::{parse_task_id, TaskId};
+
+const INSTRUCTIONS: &str = "\
+# Edit the tasks below, then save and close the editor.
+# [x] marks a done task. Delete a line to remove its task, and add a line
+# like \"[ ] Buy groceries\" for a new one. Lines starting with # are ignored.
+";
+
+/// A task line after editing; new tasks have no id
+#[derive(Debug, PartialEq)]
+struct Line {
+    id: Option<TaskId>,
+    done: bool,
+    label: String,
+}
+
+/// One change to make, in the order they have to be made
+#[derive(Debug, PartialEq)]
+enum Change {
+    Remove(u32),
+    Rename(u32, String),
+    Check(u32, bool),
+    Add(String, bool),
+}
+
+/// Opens `tasks` in the user's editor and applies whatever they changed,
+/// following the same rules as the individual commands
+pub fn run(store: &mut Store, tasks: Vec<Task>) -> Result<(), ProgressError> {
+    // the editor may stay open a long time, so nothing is locked meanwhile
+    store.unlock();
+    let path = create_temp_file(&render(&tasks))?;
+
+    let edited = open_editor(&path).and_then(|_| Ok(fs::read_to_string(&path)?));
+    let changes = edited.and_then(|content| plan(&tasks, &parse(&content)?));
+    let changes = match changes {
+        Ok(changes) => changes,
+        Err(e) => {
+            // keep the edits around so they aren't lost
+            return Err(ProgressError::Usage(format!(
+                "{}, nothing was changed. Your edits are in {}",
+                e,
+                path.display()
+            )));
+        }
+    };
+    fs::remove_file(&path)?;
+
+    if changes.is_empty() {
+        println!("Nothing changed");
+        return Ok(());
+    }
+
+    let failed = store.locked(LockMode::Exclusive, |store| {
+        let mut failed = 0;
+        for change in &changes {
+            match apply(store, change) {
+                Ok(message) => println!("{}", message.green()),
+                Err(e) => {
+                    failed += 1;
+                    println!("{}", e.to_string().red());
+                }
+            }
+        }
+        store.save()?;
+        Ok(failed)
+    })?;
+    if failed > 0 {
+        return Err(ProgressError::PartialFailure {
+            failed,
+            total: changes.len(),
+            saved: true,
+        });
+    }
+    return Ok(());
+}
+
+/// Writes `content` to a new file only we can read in the temp directory.
+/// The file must not exist yet, so nobody can plant one there for us to use.
+fn create_temp_file(content: &str) -> io::Result<PathBuf> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    for attempt in 0..100u32 {
+        let name = format!(
+            "progress-edit-{}-{:x}.txt",
+            process::id(),
+            nanos.wrapping_add(attempt)
+        );
+        let path = std::env::temp_dir().join(name);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    return Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no free name for the file to edit",
+    ));
+}
+
+fn open_editor(path: &std::path::Path) -> Result<(), ProgressError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| default_editor().to_string());
+    // the variable can carry arguments, like `code --wait`
+    let words = shlex::split(&editor)
+        .filter(|words| !words.is_empty())
+        .ok_or_else(|| ProgressError::Usage(format!("Can't run editor \"{}\"", editor)))?;
+
+    let status = process::Command::new(&words[0])
+        .args(&words[1..])
+        .arg(path)
+        .status()
+        .map_err(|e| ProgressError::Usage(format!("Can't run editor \"{}\": {}", editor, e)))?;
+    if !status.success() {
+        return Err(ProgressError::Usage(format!(
+            "Editor \"{}\" exited with {}",
+            editor, status
+        )));
+    }
+    return Ok(());
+}
+
+fn default_editor() -> &'static str {
+    if cfg!(windows) {
+        return "notepad";
+    }
+    return "vi";
+}
+
+fn render(tasks: &[Task]) -> String {
+    let mut content = INSTRUCTIONS.to_string();
+    for task in tasks {
+        content.push_str(&format!(
+            "{} [{}] {}\n",
+            task.key(),
+            if task.done { "x" } else { " " },
+            escape(&task.label)
+        ));
+    }
+    return content;
+}
+
+fn parse(content: &str) -> Result<Vec<Line>, ProgressError> {
+    let mut lines = vec![];
+    for (index, text) in content.lines().enumerate() {
+        // only the line's indentation goes, labels keep their own spaces
+        let text = text.trim_start();
+        if text.trim_end().is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let fail = |reason: &str| ProgressError::Usage(format!("line {}: {}", index + 1, reason));
+
+        // any project's prefix, like TSK-3 or WORK-12
+        let (id, rest) = match text.split_once(' ') {
+            Some((word, rest)) => match parse_task_id(word) {
+                Ok(id) => (Some(id), rest.trim_start()),
+                Err(_) => (None, text),
+            },
+            None => (None, text),
+        };
+
+        let (done, label) = if let Some(label) = rest.strip_prefix("[x]") {
+            (true, label)
+        } else if let Some(label) = rest.strip_prefix("[X]") {
+            (true, label)
+        } else if let Some(label) = rest.strip_prefix("[ ]") {
+            (false, label)
+        } else if let Some(label) = rest.strip_prefix("[]") {
+            (false, label)
+        } else if id.is_some() {
+            return Err(fail("expected [ ] or [x] after the task id"));
+        } else {
+            (false, rest)
+        };
+        // the space `render` puts after the box
+        let label = label.strip_prefix(' ').unwrap_or(label);
+
+        if label.trim().is_empty() {
+            return Err(fail("the label is empty"));
+        }
+        lines.push(Line {
+            id,
+            done,
+            label: unescape(label),
+        });
+    }
+    return Ok(lines);
+}
+
+/// Works out what changed between the tasks that were opened and the edited
+/// lines. An unchecked task is renamed after it's unchecked, and a checked
+/// one before, since finished tasks can't be renamed.
+fn plan(tasks: &[Task], lines: &[Line]) -> Result<Vec<Change>, ProgressError> {
+    // the task each line is for, none for new ones
+    let mut edited: Vec<Option<u32>> = vec![];
+    for line in lines {
+        let id = match &line.id {
+            Some(id) => id,
+            None => {
+                edited.push(None);
+                continue;
+            }
+        };
+        let task = tasks.iter().find(|task| id.matches(task)).ok_or_else(|| {
+            ProgressError::Usage(format!("{} wasn't one of the tasks being edited", id))
+        })?;
+        if edited.contains(&Some(task.id)) {
+            return Err(ProgressError::Usage(format!(
+                "{} appears more than once",
+                id
+            )));
+        }
+        edited.push(Some(task.id));
+    }
+
+    let mut changes = vec![];
+    for task in tasks {
+        let line = lines
+            .iter()
+            .zip(&edited)
+            .find(|(_, id)| **id == Some(task.id))
+            .map(|(line, _)| line);
+        let line = match line {
+            Some(line) => line,
+            None => {
+                changes.push(Change::Remove(task.id));
+                continue;
+            }
+        };
+
+        if task.done && !line.done {
+            changes.push(Change::Check(task.id, false));
+        }
+        if task.label != line.label {
+            changes.push(Change::Rename(task.id, line.label.clone()));
+        }
+        if !task.done && line.done {
+            changes.push(Change::Check(task.id, true));
+        }
+    }
+
+    for line in lines.iter().filter(|line| line.id.is_none()) {
+        changes.push(Change::Add(line.label.clone(), line.done));
+    }
+    return Ok(changes);
+}
+
+fn apply(store: &mut Store, change: &Change) -> Result<String, ProgressError> {
+    match change {
+        Change::Remove(id) => {
+            let key = store.task_key(*id);
+            store.remove_task(*id)?;
+            return Ok(format!("{} removed", key));
+        }
+        Change::Rename(id, label) => {
+            store.relabel_task(*id, label)?;
+            return Ok(format!("{} renamed", store.task_key(*id)));
+        }
+        Change::Check(id, check) => {
+            let checked = store.toggle_check_task(*id, *check)?;
+            let done = if *check { "checked" } else { "unchecked" };
+            if let Some(warning) = store.blocked_warning(&checked) {
+                return Ok(format!("{} {}, it {}", store.task_key(*id), done, warning));
+            }
+            return Ok(format!("{} {}", store.task_key(*id), done));
+        }
+        Change::Add(label, done) => {
+            let id = store.create_task(Task {
+                label: label.clone(),
+                ..Task::default()
+            })?;
+            if *done {
+                store.toggle_check_task(id, true)?;
+            }
+            return Ok(format!("{} added", store.task_key(id)));
+        }
+    }
+}
+
+/// Labels are shown one per line, so line breaks (and backslashes, to tell
+/// them apart) are escaped
+fn escape(label: &str) -> String {
+    return label
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+}
+
+fn unescape(text: &str) -> String {
+    let mut label = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            label.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => label.push('\n'),
+            Some('r') => label.push('\r'),
+            Some('\\') => label.push('\\'),
+            Some(other) => {
+                label.push('\\');
+                label.push(other);
+            }
+            None => label.push('\\'),
+        }
+    }
+    return label;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u32, done: bool, label: &str) -> Task {
+        return Task {
+            id,
+            done,
+            label: label.to_string(),
+            ..Task::default()
+        };
+    }
+
+    #[test]
+    fn rendered_tasks_parse_back_unchanged() {
+        let tasks = vec![
+            task(3, false, "Say \"hi\" to Sam"),
+            task(4, true, "two\nlines with a \\ backslash"),
+            task(5, false, "  padded on both sides "),
+        ];
+        let lines = parse(&render(&tasks)).unwrap();
+        assert_eq!(plan(&tasks, &lines).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn plans_every_kind_of_change() {
+        let tasks = vec![
+            task(1, false, "Buy milk"),
+            task(2, true, "Call the bank"),
+            task(3, false, "Write report"),
+            task(4, false, "Pay rent"),
+        ];
+        let content = "\
+TSK-1 [x] Buy oat milk
+tsk-2 [ ] Call the bank again
+# TSK-3 is gone
+TSK-4 [ ] Pay rent
+[x] Water the plants
+Plan the week
+";
+        let changes = plan(&tasks, &parse(content).unwrap()).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Rename(1, "Buy oat milk".to_string()),
+                Change::Check(1, true),
+                Change::Check(2, false),
+                Change::Rename(2, "Call the bank again".to_string()),
+                Change::Remove(3),
+                Change::Add("Water the plants".to_string(), true),
+                Change::Add("Plan the week".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_ids_that_were_not_opened() {
+        let tasks = vec![task(1, false, "Buy milk")];
+        for content in ["TSK-9 [ ] Other", "TSK-1 [ ] a\nTSK-1 [ ] b"] {
+            assert!(
+                plan(&tasks, &parse(content).unwrap()).is_err(),
+                "{}",
+                content
+            );
+        }
+        assert!(parse("TSK-1 Buy milk").is_err());
+        let id = parse("work-7 [ ] Ship it").unwrap().remove(0).id.unwrap();
+        assert_eq!((id.prefix.as_str(), id.number), ("WORK", 7));
+        // the number alone isn't enough, the prefix has to match too
+        assert!(plan(&tasks, &parse("WORK-1 [ ] Buy milk").unwrap()).is_err());
+        assert_eq!(parse("Follow-up call").unwrap()[0].id, None);
+        assert!(parse("TSK-1 [x]").is_err());
+    }
+}
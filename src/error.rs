@@ -0,0 +1,127 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum ProgressError {
+    /// Bad arguments on the command line
+    Usage(String),
+    InvalidTaskId(String),
+    /// An import file that can't be read
+    Import(String),
+    /// A `list` query that can't be parsed
+    Query(String),
+    /// Carries the task's key, like WORK-3
+    NotFound(String),
+    /// The change is only allowed on the day the task was created or checked
+    EditWindowClosed(String),
+    /// These carry the task's key, like WORK-3
+    TaskFinished(String),
+    AlreadyDone(String),
+    NotDone(String),
+    /// Another process holds the store. Carries the PIDs of the processes holding the store, when known
+    Locked(Vec<u32>),
+    Parse {
+        line: usize,
+        content: String,
+        reason: String,
+    },
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    Io(io::Error),
+    /// Some tasks of a bulk change failed, `saved` tells if the rest were kept
+    PartialFailure {
+        failed: usize,
+        total: usize,
+        saved: bool,
+    },
+}
+
+impl ProgressError {
+    /// Process exit code, so scripts can tell failures apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ProgressError::Usage(_)
+            | ProgressError::InvalidTaskId(_)
+            | ProgressError::Import(_)
+            | ProgressError::Query(_) => 2,
+            ProgressError::NotFound(_) => 3,
+            ProgressError::EditWindowClosed(_) => 4,
+            ProgressError::TaskFinished(_)
+            | ProgressError::AlreadyDone(_)
+            | ProgressError::NotDone(_) => 5,
+            ProgressError::Locked(_) => 6,
+            ProgressError::Parse { .. } | ProgressError::UnsupportedVersion { .. } => 7,
+            ProgressError::Io(_) => 8,
+            ProgressError::PartialFailure { .. } => 9,
+        }
+    }
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressError::Usage(message) => write!(f, "{}", message),
+            ProgressError::InvalidTaskId(id) => {
+                write!(f, "Invalid task id \"{}\", expected something like TSK-1", id)
+            }
+            ProgressError::Import(reason) => write!(f, "Could not import tasks: {}", reason),
+            ProgressError::Query(reason) => write!(f, "Invalid query: {}", reason),
+            ProgressError::NotFound(id) => write!(f, "Task {} does not exist", id),
+            ProgressError::EditWindowClosed(message) => write!(f, "{}", message),
+            ProgressError::TaskFinished(id) => {
+                write!(f, "Task {} is finished and can't be changed", id)
+            }
+            ProgressError::AlreadyDone(id) => write!(f, "Task {} is already done", id),
+            ProgressError::NotDone(id) => write!(f, "Task {} is not completed yet", id),
+            ProgressError::Locked(pids) => match pids.as_slice() {
+                [] => write!(f, "Store is locked by another process"),
+                [pid] => write!(f, "Store is locked by process {}", pid),
+                pids => write!(
+                    f,
+                    "Store is locked by processes {}",
+                    pids.iter()
+                        .map(|pid| pid.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            },
+            ProgressError::Parse {
+                line,
+                content,
+                reason,
+            } => {
+                write!(f, "Store is corrupted at line {}: {}", line, reason)?;
+                if !content.is_empty() {
+                    write!(f, "\n  {} | {}", line, content)?;
+                }
+                write!(f, "\nRun `progress doctor` to inspect and repair it.")
+            }
+            ProgressError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "This store was written by a newer version of progress (store format v{}, this build reads up to v{}). Please upgrade progress.",
+                found, supported
+            ),
+            ProgressError::Io(e) => write!(f, "{}", e),
+            ProgressError::PartialFailure {
+                failed,
+                total,
+                saved,
+            } => {
+                write!(f, "{} of {} task(s) could not be changed", failed, total)?;
+                if !saved {
+                    write!(f, ", nothing was saved")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgressError {}
+
+impl From<io::Error> for ProgressError {
+    fn from(error: io::Error) -> Self {
+        ProgressError::Io(error)
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::ds::STORE_FILE;
+use crate::error::ProgressError;
+
+const STORE_ENV: &str = "PROGRESS_STORE";
+const PROJECT_FILE: &str = ".progress";
+
+pub enum StoreSource {
+    Flag,
+    Env,
+    ProjectFile(PathBuf),
+    Legacy,
+    Default,
+}
+
+pub struct StoreLocation {
+    pub path: PathBuf,
+    pub source: StoreSource,
+}
+
+impl fmt::Display for StoreSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreSource::Flag => write!(f, "set with the --store flag"),
+            StoreSource::Env => write!(f, "set with the {} environment variable", STORE_ENV),
+            StoreSource::ProjectFile(file) => write!(f, "found {}", file.display()),
+            StoreSource::Legacy => write!(f, "existing store next to the executable"),
+            StoreSource::Default => write!(f, "default data directory"),
+        }
+    }
+}
+
+/// Resolves which store file to use, in order of precedence:
+/// the --store flag, the PROGRESS_STORE variable, a .progress file in the
+/// current directory or any of its parents, and finally the data directory.
+pub fn resolve(flag: Option<&str>) -> Result<StoreLocation, ProgressError> {
+    if let Some(path) = flag {
+        return Ok(StoreLocation {
+            path: store_file(PathBuf::from(path)),
+            source: StoreSource::Flag,
+        });
+    }
+
+    if let Ok(path) = env::var(STORE_ENV) {
+        if !path.trim().is_empty() {
+            return Ok(StoreLocation {
+                path: store_file(PathBuf::from(path)),
+                source: StoreSource::Env,
+            });
+        }
+    }
+
+    let cwd = env::current_dir()?;
+    if let Some(location) = find_project_file(&cwd)? {
+        return Ok(location);
+    }
+
+    let default = data_dir().join(STORE_FILE);
+
+    // stores used to live next to the binary, keep using one if it's there
+    // so upgrading doesn't leave the old tasks behind
+    if !default.exists() {
+        if let Some(legacy) = legacy_store() {
+            return Ok(StoreLocation {
+                path: legacy,
+                source: StoreSource::Legacy,
+            });
+        }
+    }
+
+    return Ok(StoreLocation {
+        path: default,
+        source: StoreSource::Default,
+    });
+}
+
+/// A path pointing at a directory means the store file inside it
+fn store_file(path: PathBuf) -> PathBuf {
+    if path.is_dir() {
+        path.join(STORE_FILE)
+    } else {
+        path
+    }
+}
+
+/// Walks up from `start` looking for a .progress file. An empty file keeps the
+/// store next to it, otherwise its first line is a path relative to the file.
+fn find_project_file(start: &Path) -> Result<Option<StoreLocation>, ProgressError> {
+    for dir in start.ancestors() {
+        let file = dir.join(PROJECT_FILE);
+        if !file.is_file() {
+            continue;
+        }
+
+        let content = std::fs::read_to_string(&file)?;
+        let target = content.lines().next().unwrap_or("").trim();
+        let path = if target.is_empty() {
+            dir.join(STORE_FILE)
+        } else {
+            store_file(dir.join(target))
+        };
+
+        return Ok(Some(StoreLocation {
+            path,
+            source: StoreSource::ProjectFile(file),
+        }));
+    }
+    return Ok(None);
+}
+
+fn data_dir() -> PathBuf {
+    if let Ok(dir) = env::var("XDG_DATA_HOME") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir).join("progress");
+        }
+    }
+    if let Ok(home) = env::var("HOME") {
+        return PathBuf::from(home).join(".local/share/progress");
+    }
+    if let Ok(appdata) = env::var("APPDATA") {
+        return PathBuf::from(appdata).join("progress");
+    }
+    return PathBuf::from(".");
+}
+
+fn legacy_store() -> Option<PathBuf> {
+    let binary_file_path = env::current_exe().ok()?;
+    let path = binary_file_path.parent()?.join(STORE_FILE);
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use crate::ds::sibling_path;
+use crate::error::ProgressError;
+
+pub const LOCK_TIMEOUT_ENV: &str = "PROGRESS_LOCK_TIMEOUT";
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LockMode {
+    /// Many readers may hold the store at once
+    Shared,
+    /// Held while a command may change the store
+    Exclusive,
+}
+
+/// Advisory lock on `progress.store.lock`, released when dropped.
+/// Holders list their PID in `progress.store.holders`, one per line, so
+/// waiting processes can say who they are waiting on.
+pub struct StoreLock {
+    file: Option<File>,
+    holders_path: PathBuf,
+}
+
+impl StoreLock {
+    pub fn acquire(
+        store_path: &Path,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<Self, ProgressError> {
+        let lock_path = sibling_path(store_path, "lock");
+        let holders_path = sibling_path(store_path, "holders");
+
+        if let Some(parent) = lock_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                if mode == LockMode::Shared {
+                    // nothing has been saved yet, so there's nothing to guard
+                    return Ok(StoreLock {
+                        file: None,
+                        holders_path,
+                    });
+                }
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)?;
+
+        let started = Instant::now();
+        loop {
+            let attempt = match mode {
+                LockMode::Shared => file.try_lock_shared(),
+                LockMode::Exclusive => file.try_lock(),
+            };
+
+            match attempt {
+                Ok(()) => break,
+                Err(fs::TryLockError::WouldBlock) => {
+                    if started.elapsed() >= timeout {
+                        let holders = edit_holders(&holders_path, |_| {}).unwrap_or_default();
+                        return Err(ProgressError::Locked(holders));
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        edit_holders(&holders_path, |holders| {
+            // holding it alone, any other PID was left by a process that died
+            if mode == LockMode::Exclusive {
+                holders.clear();
+            }
+            holders.push(process::id());
+        })?;
+
+        return Ok(StoreLock {
+            file: Some(file),
+            holders_path,
+        });
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = edit_holders(&self.holders_path, |holders| {
+                if let Some(index) = holders.iter().position(|pid| *pid == process::id()) {
+                    holders.remove(index);
+                }
+            });
+            let _ = file.unlock();
+        }
+    }
+}
+
+/// Changes the list of processes holding the store, returning the others
+fn edit_holders(path: &Path, change: impl FnOnce(&mut Vec<u32>)) -> io::Result<Vec<u32>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    // only held for the read and write below, it's released on close
+    file.lock()?;
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let mut holders: Vec<u32> = content
+        .lines()
+        .filter_map(|line| line.trim().parse::<u32>().ok())
+        .collect();
+    change(&mut holders);
+
+    let mut content = String::new();
+    for pid in &holders {
+        content.push_str(&format!("{}\n", pid));
+    }
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(content.as_bytes())?;
+
+    holders.retain(|pid| *pid != process::id());
+    return Ok(holders);
+}
+
+/// Reads the lock timeout in seconds from the flag value or PROGRESS_LOCK_TIMEOUT
+pub fn lock_timeout(flag: Option<&str>) -> Result<Duration, ProgressError> {
+    let value = match flag {
+        Some(value) => value.to_string(),
+        None => match std::env::var(LOCK_TIMEOUT_ENV) {
+            Ok(value) => value,
+            Err(_) => return Ok(DEFAULT_LOCK_TIMEOUT),
+        },
+    };
+
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+            return Ok(Duration::from_secs_f64(seconds));
+        }
+        _ => {
+            return Err(ProgressError::Usage(format!(
+                "Invalid lock timeout \"{}\"",
+                value
+            )))
+        }
+    }
+}
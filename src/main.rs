@@ -1,109 +1,78 @@
-use std::path::Path;
+#![allow(clippy::needless_return)]
 
-use chrono::Local;
+use clap::Parser;
+use cli::{Cli, Command};
 use colored::Colorize;
-use ds::{Store, Task};
-use utils::print_help;
-
+use ds::Store;
+use error::ProgressError;
+use lock::LockMode;
+
+mod cli;
+mod commands;
+mod completions;
+mod dates;
+mod doctor;
 mod ds;
+mod edit;
+mod error;
+mod location;
+mod lock;
+mod migrations;
+mod query;
+mod report;
+mod repository;
+mod search;
+mod shell;
+#[cfg(feature = "sqlite")]
+mod sqlite_repository;
+mod text_repository;
+mod transfer;
+mod tui;
 mod utils;
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    let binary_name = args[0].clone();
-    let binary_file_path = std::env::current_exe().expect("Could not get binary's directory");
-    let mut bin_dir_ansestor = binary_file_path.ancestors();
-
-    bin_dir_ansestor.next(); // first iteration of ansestor
-    let binary_directory = bin_dir_ansestor.next().unwrap_or(Path::new("."));
+    // completion scripts call back in here to ask for candidates
+    completions::complete_if_requested();
 
-    let mut store =
-        Store::open(binary_directory.to_str().unwrap()).expect("Could not create store");
-
-    if args.len() == 1 {
-        store.show_info();
-        return;
+    if let Err(e) = run() {
+        eprintln!("{}", e.to_string().red());
+        std::process::exit(e.exit_code());
     }
+}
 
-    match args[1].as_str() {
-        "--help" => {
-            print_help(&binary_name);
+fn run() -> Result<(), ProgressError> {
+    let cli = Cli::parse();
+    let json = cli.json;
+    let lock_timeout = lock::lock_timeout(cli.lock_timeout.as_deref())?;
+    let location = location::resolve(cli.store.as_deref())?;
+
+    // only commands that change the store need it to themselves
+    let lock_mode = match &cli.command {
+        Some(Command::Where) => {
+            println!("{}", location.path.display());
+            println!("({})", location.source);
+            return Ok(());
         }
-        "--minimal" => {
-            store.show_info_basic();
+        Some(Command::Doctor { fix }) => {
+            return doctor::run(&location.path, *fix, lock_timeout);
         }
-        "--add" => {
-            if let Some(task_label) = args.get(2) {
-                let now = Local::now().timestamp();
-                let id = store.metadata.last_task_id;
-                let task = Task {
-                    id,
-                    done: false,
-                    label: task_label.clone(),
-                    date_checked: None,
-                    date_created: now,
-                };
-
-                store.add_task(task);
-                println!("Task (tsk-{}) added to store", id);
-            } else {
-                println!("No task in entry");
-            }
+        Some(Command::Completions { shell }) => {
+            return completions::write_script(shell);
         }
-        "--task" => {
-            let id = args.get(2).expect("Expected task id");
-            let command = args.get(3);
-
-            let ids = id.split('-').collect::<Vec<&str>>();
-
-            if ids.len() != 2 || ids[0].to_lowercase() != "tsk" {
-                println!("Invalid task id");
-                panic!()
-            }
-
-            let id = ids[1].parse::<u32>().expect("Invalid task id suffix");
-
-            if command.is_none() {
-                store.show_task_information(id);
-                return;
-            }
-
-            let command = command.unwrap();
+        Some(Command::Date { expression }) => {
+            return commands::preview_date(expression, json);
+        }
+        Some(command) if command.changes_store() => LockMode::Exclusive,
+        _ => LockMode::Shared,
+    };
 
-            match command.as_str() {
-                "--remove" => {
-                    store.remove_task(id).unwrap();
-                }
-                "--check" => {
-                    store.toggle_check_task(id, true).unwrap();
-                }
-                "--uncheck" => {
-                    store.toggle_check_task(id, false).unwrap();
-                }
-                "--rename" => {
-                    if let Some(label) = args.get(4) {
-                        if label.trim().len() == 0 {
-                            println!("{}", "Need to include label".red());
-                            return;
-                        }
-                        store.relabel_task(id, label).unwrap();
-                    } else {
-                        println!("{}", "Need to include label".red());
-                    }
-                }
-                _ => {
-                    println!("{}", "Invalid task command".red());
-                    print_help(&binary_name);
-                    panic!();
-                }
-            }
+    let mut store = Store::open(&location.path, lock_mode, lock_timeout)?;
+    store.set_scope(cli.project.as_deref())?;
 
-            store.save();
-        }
-        _ => {
-            print_help(&binary_name);
-        }
+    if let Some(Command::Shell) = cli.command {
+        return shell::run(&mut store, &location.path, cli.project.as_deref());
     }
+    return commands::execute(&mut store, cli.command, json);
 }
 
 // progress
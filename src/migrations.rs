@@ -0,0 +1,184 @@
+use crate::error::ProgressError;
+use crate::utils::quote_field;
+
+/// Format written by this build. Bump it whenever the store layout changes and
+/// add the step that upgrades the previous version to `MIGRATIONS`.
+pub const STORE_VERSION: u32 = 9;
+
+pub const VERSION_HEADER: &str = ":version";
+
+/// A line of a store body with the file line it came from, so parse errors
+/// in a migrated store still point into the file
+type Line = (usize, String);
+
+type Migration = fn(Vec<Line>) -> Result<Vec<Line>, ProgressError>;
+
+/// `MIGRATIONS[n]` upgrades the body of a version `n + 1` store to `n + 2`
+const MIGRATIONS: [Migration; (STORE_VERSION - 1) as usize] = [
+    v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9,
+];
+
+/// Splits a store into its format version, the body after the header and the
+/// line the body starts on. Stores without a header predate versioning and
+/// are version 1.
+pub fn split_header(content: &str) -> Result<(u32, &str, usize), ProgressError> {
+    let (first_line, rest) = content.split_once('\n').unwrap_or((content, ""));
+
+    if let Some(version) = first_line.trim().strip_prefix(VERSION_HEADER) {
+        let version = match version.trim().parse::<u32>() {
+            Ok(version) if version > 0 => version,
+            _ => {
+                return Err(ProgressError::Parse {
+                    line: 1,
+                    content: first_line.to_string(),
+                    reason: format!("Invalid store version \"{}\"", version.trim()),
+                })
+            }
+        };
+        return Ok((version, rest, 2));
+    }
+
+    return Ok((1, content, 1));
+}
+
+/// Runs every migration from `version` up to the current format, returning
+/// the upgraded body and the file line each of its lines came from. Lines a
+/// migration adds count as the line they follow. `first_line` is the file
+/// line the body starts on.
+pub fn migrate(
+    body: &str,
+    version: u32,
+    first_line: usize,
+) -> Result<(String, Vec<usize>), ProgressError> {
+    let mut lines: Vec<Line> = body
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| (first_line + index, line.to_string()))
+        .collect();
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        lines = migration(lines)?;
+    }
+    let (numbers, lines): (Vec<usize>, Vec<String>) = lines.into_iter().unzip();
+    return Ok((lines.join("\n"), numbers));
+}
+
+/// v2 only added the version header, the body is unchanged
+fn v1_to_v2(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(body);
+}
+
+/// v3 quotes and escapes task labels so they can hold any text
+fn v2_to_v3(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    let mut upgraded: Vec<Line> = vec![];
+    // position inside the current task block, blank lines don't count
+    let mut task_line: Option<u8> = None;
+
+    for (number, line) in body {
+        if line.is_empty() {
+            upgraded.push((number, line));
+            continue;
+        }
+
+        match line.as_str() {
+            ":task" => task_line = Some(0),
+            ":metadata" | ":end" => task_line = None,
+            _ => {
+                if let Some(n) = task_line {
+                    task_line = Some(n + 1);
+                    // the label was the third line of a task
+                    if n == 2 {
+                        upgraded.push((number, quote_field(line.trim())));
+                        continue;
+                    }
+                }
+            }
+        }
+
+        upgraded.push((number, line));
+    }
+
+    return Ok(upgraded);
+}
+
+/// v4 adds the priority line after the check date, `-` for none
+fn v3_to_v4(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 5, "-"));
+}
+
+/// v5 adds the due date line after the priority, `-` for none
+fn v4_to_v5(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 6, "-"));
+}
+
+/// v6 adds the tags line after the due date, `-` for none
+fn v5_to_v6(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 7, "-"));
+}
+
+/// v7 adds the project line after the tags, `-` for the inbox. Projects get
+/// their own blocks, which older stores don't have.
+fn v6_to_v7(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 8, "-"));
+}
+
+/// v8 adds the parent task line after the project, `-` for none
+fn v7_to_v8(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 9, "-"));
+}
+
+/// v9 adds the line of blocking task ids after the parent, `-` for none
+fn v8_to_v9(body: Vec<Line>) -> Result<Vec<Line>, ProgressError> {
+    return Ok(add_task_field(body, 10, "-"));
+}
+
+/// Inserts `value` as a new line after the first `fields` lines of every task.
+/// Blocks too short to have them are left alone for the parser to report.
+fn add_task_field(body: Vec<Line>, fields: u8, value: &str) -> Vec<Line> {
+    let mut upgraded: Vec<Line> = vec![];
+    let mut task_line: Option<u8> = None;
+
+    for (number, line) in body {
+        let is_field = match line.as_str() {
+            "" => false,
+            ":task" => {
+                task_line = Some(0);
+                false
+            }
+            ":metadata" | ":end" => {
+                task_line = None;
+                false
+            }
+            _ => true,
+        };
+        upgraded.push((number, line));
+
+        if let Some(n) = task_line.filter(|_| is_field) {
+            task_line = Some(n + 1);
+            if n + 1 == fields {
+                upgraded.push((number, value.to_string()));
+            }
+        }
+    }
+
+    return upgraded;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrated_lines_keep_their_file_line() {
+        let body = ":metadata\n1\n:end\n:task\n0\n[]\nlabel\n100\nbad\n:end\n";
+        let (body, numbers) = migrate(body, 1, 1).unwrap();
+        let lines: Vec<&str> = body.split('\n').collect();
+        assert_eq!(lines.len(), numbers.len());
+
+        // the check date is still on file line 9, however many fields follow
+        let index = lines.iter().position(|line| *line == "bad").unwrap();
+        assert_eq!(numbers[index], 9);
+        assert_eq!(lines[index + 1..index + 7], ["-"; 6]);
+        assert_eq!(lines[index + 7], ":end");
+        assert_eq!(numbers[index + 7], 10);
+    }
+}
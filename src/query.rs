@@ -0,0 +1,499 @@
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime};
+use regex::Regex;
+
+use crate::dates::{self, local_date};
+use crate::ds::{Priority, Task};
+use crate::error::ProgressError;
+use crate::utils::parse_tag;
+
+/// A filter over tasks, parsed from `progress list` arguments like
+/// `status:open and (created:>2026-01-01 or label~report)`
+#[derive(Debug)]
+pub enum Query {
+    All,
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Done(bool),
+    Created(DateRange),
+    /// Never matches unchecked tasks
+    Checked(DateRange),
+    Label(Regex),
+    Priority(Priority),
+    /// By local day, never matches tasks without a due date
+    Due(DateRange),
+    Tag(String),
+}
+
+/// Days from `from` to `until`, both included. An unset end is open.
+#[derive(Debug, PartialEq)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SortKey {
+    Id,
+    Created,
+    Checked,
+    Label,
+    Status,
+    Priority,
+    Due,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    now: NaiveDateTime,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, ProgressError> {
+        return Query::parse_at(input, Local::now().naive_local());
+    }
+
+    /// Parses with relative dates like `this-week` counted from `now`
+    pub fn parse_at(input: &str, now: NaiveDateTime) -> Result<Query, ProgressError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            position: 0,
+            now,
+        };
+        if parser.tokens.is_empty() {
+            return Ok(Query::All);
+        }
+
+        let query = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(ProgressError::Query(format!(
+                "unexpected {}",
+                describe(token)
+            )));
+        }
+        return Ok(query);
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Query::All => true,
+            Query::And(left, right) => left.matches(task) && right.matches(task),
+            Query::Or(left, right) => left.matches(task) || right.matches(task),
+            Query::Not(query) => !query.matches(task),
+            Query::Done(done) => task.done == *done,
+            Query::Created(range) => range.contains(task.date_created),
+            Query::Checked(range) => task
+                .date_checked
+                .map(|date| range.contains(date))
+                .unwrap_or(false),
+            Query::Label(regex) => regex.is_match(&task.label),
+            Query::Priority(priority) => task.priority == *priority,
+            Query::Tag(tag) => task.tags.contains(tag),
+            Query::Due(range) => task
+                .date_due
+                .and_then(local_date)
+                .map(|date| range.contains_date(date))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl DateRange {
+    /// Task dates are compared by their UTC day
+    fn contains(&self, timestamp: i64) -> bool {
+        let date = match DateTime::from_timestamp(timestamp, 0) {
+            Some(date) => date.date_naive(),
+            None => return false,
+        };
+        return self.contains_date(date);
+    }
+
+    fn contains_date(&self, date: NaiveDate) -> bool {
+        return self.from.map(|from| date >= from).unwrap_or(true)
+            && self.until.map(|until| date <= until).unwrap_or(true);
+    }
+
+    /// Reads a named period, or a date expression (see `dates::parse_at`) like
+    /// `2026-01-01` or `tomorrow` with an optional `>`, `>=`, `<` or `<=` in front
+    fn parse(value: &str, now: NaiveDateTime) -> Result<DateRange, String> {
+        let today = now.date();
+        let between = |from: NaiveDate, until: NaiveDate| DateRange {
+            from: Some(from),
+            until: Some(until),
+        };
+        let week_start = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        let month_start = today.with_day(1).unwrap();
+
+        match value {
+            "today" => return Ok(between(today, today)),
+            "yesterday" => {
+                let yesterday = today - Days::new(1);
+                return Ok(between(yesterday, yesterday));
+            }
+            "this-week" => return Ok(between(week_start, week_start + Days::new(6))),
+            "last-week" => {
+                return Ok(between(
+                    week_start - Days::new(7),
+                    week_start - Days::new(1),
+                ))
+            }
+            "this-month" => {
+                let next_month = month_start + Months::new(1);
+                return Ok(between(month_start, next_month - Days::new(1)));
+            }
+            "last-month" => {
+                return Ok(between(
+                    month_start - Months::new(1),
+                    month_start - Days::new(1),
+                ))
+            }
+            _ => {}
+        }
+
+        let split = value
+            .find(|c: char| !matches!(c, '<' | '>' | '='))
+            .unwrap_or(value.len());
+        let (operator, date) = value.split_at(split);
+        let date = dates::parse_at(date, now)
+            .map_err(|e| e.to_string())?
+            .date();
+
+        match operator {
+            "" | "=" => return Ok(between(date, date)),
+            ">" => {
+                return Ok(DateRange {
+                    from: Some(date + Days::new(1)),
+                    until: None,
+                })
+            }
+            ">=" => {
+                return Ok(DateRange {
+                    from: Some(date),
+                    until: None,
+                })
+            }
+            "<" => {
+                return Ok(DateRange {
+                    from: None,
+                    until: Some(date - Days::new(1)),
+                })
+            }
+            "<=" => {
+                return Ok(DateRange {
+                    from: None,
+                    until: Some(date),
+                })
+            }
+            _ => return Err(format!("unknown comparison \"{}\"", operator)),
+        }
+    }
+}
+
+impl Parser {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    /// or := and ("or" and)*
+    fn or(&mut self) -> Result<Query, ProgressError> {
+        let mut query = self.and()?;
+        while self.peek_keyword("or") {
+            self.position += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        return Ok(query);
+    }
+
+    /// and := not ("and"? not)*, terms next to each other are and-ed
+    fn and(&mut self) -> Result<Query, ProgressError> {
+        let mut query = self.not()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.position += 1;
+            } else if matches!(self.tokens.get(self.position), None | Some(Token::Close))
+                || self.peek_keyword("or")
+            {
+                return Ok(query);
+            }
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+    }
+
+    /// not := "not" not | "(" or ")" | term
+    fn not(&mut self) -> Result<Query, ProgressError> {
+        let token = match self.tokens.get(self.position) {
+            Some(token) => token,
+            None => return Err(ProgressError::Query("query ends too early".to_string())),
+        };
+        self.position += 1;
+
+        match token {
+            Token::Word(word) if word.eq_ignore_ascii_case("not") => {
+                return Ok(Query::Not(Box::new(self.not()?)));
+            }
+            Token::Word(word) => {
+                let word = word.clone();
+                return self.term(&word);
+            }
+            Token::Open => {
+                let query = self.or()?;
+                if self.tokens.get(self.position) != Some(&Token::Close) {
+                    return Err(ProgressError::Query("missing )".to_string()));
+                }
+                self.position += 1;
+                return Ok(query);
+            }
+            Token::Close => return Err(ProgressError::Query("unexpected )".to_string())),
+        }
+    }
+
+    /// term := "status:" state | "priority:" level | "created:" date | "checked:" date
+    ///       | "due:" date | "tag:" tag | "label~" regex
+    fn term(&self, word: &str) -> Result<Query, ProgressError> {
+        let fail = |reason: String| ProgressError::Query(format!("{} in \"{}\"", reason, word));
+
+        if let Some(pattern) = word.strip_prefix("label~") {
+            let regex = Regex::new(pattern).map_err(|e| fail(e.to_string()))?;
+            return Ok(Query::Label(regex));
+        }
+
+        let (field, value) = word
+            .split_once(':')
+            .ok_or_else(|| fail("expected field:value".to_string()))?;
+        match field.to_lowercase().as_str() {
+            "status" => match value.to_lowercase().as_str() {
+                "open" => return Ok(Query::Done(false)),
+                "done" => return Ok(Query::Done(true)),
+                _ => return Err(fail("status is open or done".to_string())),
+            },
+            "priority" => match Priority::from_name(value) {
+                Some(priority) => return Ok(Query::Priority(priority)),
+                None => return Err(fail("priority is high, medium, low or none".to_string())),
+            },
+            "created" => {
+                return Ok(Query::Created(
+                    DateRange::parse(value, self.now).map_err(fail)?,
+                ))
+            }
+            "checked" => {
+                return Ok(Query::Checked(
+                    DateRange::parse(value, self.now).map_err(fail)?,
+                ))
+            }
+            "tag" => {
+                return Ok(Query::Tag(
+                    parse_tag(value).map_err(|e| fail(e.to_string()))?,
+                ))
+            }
+            "due" => return Ok(Query::Due(DateRange::parse(value, self.now).map_err(fail)?)),
+            _ => return Err(fail(format!("unknown field \"{}\"", field))),
+        }
+    }
+}
+
+/// Splits on whitespace and parentheses. Double quotes keep spaces and
+/// parentheses in a word, `\"` is a literal quote inside them.
+fn tokenize(input: &str) -> Result<Vec<Token>, ProgressError> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' | ' ' | '\t' | '\n' => {
+                if !word.is_empty() {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                }
+                match c {
+                    '(' => tokens.push(Token::Open),
+                    ')' => tokens.push(Token::Close),
+                    _ => {}
+                }
+            }
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"') => word.push('"'),
+                        Some(c) => {
+                            word.push('\\');
+                            word.push(c);
+                        }
+                        None => {}
+                    },
+                    Some(c) => word.push(c),
+                    None => return Err(ProgressError::Query("quote is never closed".to_string())),
+                }
+            },
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(Token::Word(word));
+    }
+    return Ok(tokens);
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+        Token::Word(word) => format!("\"{}\"", word),
+    }
+}
+
+/// Orders tasks by `key`, breaking ties by id. Unchecked tasks sort after
+/// checked ones by check date, and tasks without a due date after the rest.
+pub fn sort(tasks: &mut [Task], key: SortKey) {
+    match key {
+        SortKey::Id => tasks.sort_by_key(|task| task.id),
+        SortKey::Created => tasks.sort_by_key(|task| (task.date_created, task.id)),
+        SortKey::Checked => {
+            tasks.sort_by_key(|task| (task.date_checked.is_none(), task.date_checked, task.id))
+        }
+        SortKey::Label => tasks.sort_by(|a, b| {
+            a.label
+                .to_lowercase()
+                .cmp(&b.label.to_lowercase())
+                .then(a.id.cmp(&b.id))
+        }),
+        SortKey::Status => tasks.sort_by_key(|task| (task.done, task.id)),
+        SortKey::Priority => tasks.sort_by_key(|task| (task.priority, task.id)),
+        SortKey::Due => tasks.sort_by_key(|task| (task.date_due.is_none(), task.date_due, task.id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        return NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+    }
+
+    fn task(label: &str, created: &str, checked: Option<&str>) -> Task {
+        let timestamp = |value: &str| {
+            date(value)
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        };
+        return Task {
+            id: 0,
+            done: checked.is_some(),
+            label: label.to_string(),
+            date_created: timestamp(created),
+            date_checked: checked.map(timestamp),
+            priority: Priority::None,
+            date_due: None,
+            tags: Default::default(),
+            project: None,
+            parent: None,
+            blocked_by: Default::default(),
+        };
+    }
+
+    fn matches(query: &str, task: &Task) -> bool {
+        // a Wednesday
+        let now = date("2026-01-14").and_hms_opt(10, 30, 0).unwrap();
+        return Query::parse_at(query, now).unwrap().matches(task);
+    }
+
+    #[test]
+    fn compares_dates() {
+        let task = task("report", "2026-01-10", Some("2026-01-13"));
+        assert!(matches("created:2026-01-10", &task));
+        assert!(matches("created:>=2026-01-10", &task));
+        assert!(!matches("created:>2026-01-10", &task));
+        assert!(matches("created:<2026-01-11", &task));
+        assert!(matches("checked:this-week", &task));
+        assert!(matches("checked:yesterday", &task));
+        assert!(matches("created:last-week", &task));
+        assert!(matches("created:this-month", &task));
+        assert!(!matches("checked:last-month", &task));
+
+        let due = Task {
+            date_due: Some(task.date_created + 7 * 24 * 60 * 60),
+            ..task.clone()
+        };
+        assert!(matches("due:2026-01-17", &due));
+        assert!(matches("due:>2026-01-14", &due));
+        assert!(matches("due:this-week", &due));
+        assert!(matches("due:<=sat", &due));
+        assert!(!matches("due:\"next mon\"", &due));
+        assert!(!matches("due:last-week", &due));
+        assert!(!matches("due:>2026-01-14", &task));
+    }
+
+    #[test]
+    fn unchecked_tasks_have_no_check_date() {
+        let task = task("report", "2026-01-10", None);
+        assert!(!matches("checked:<2030-01-01", &task));
+        assert!(matches("not checked:<2030-01-01", &task));
+    }
+
+    #[test]
+    fn combines_terms() {
+        let task = task("Write the report", "2026-01-14", None);
+        assert!(matches("status:open label~report", &task));
+        assert!(matches("status:done or label~^Write", &task));
+        assert!(!matches("status:open and not label~report", &task));
+        assert!(matches(
+            "(status:done or created:today) and label~\"the report\"",
+            &task
+        ));
+        assert!(!matches(
+            "status:done or (created:today and label~\"(?i)memo\")",
+            &task
+        ));
+        assert!(matches("", &task));
+
+        let urgent = Task {
+            priority: Priority::High,
+            ..task.clone()
+        };
+        assert!(matches("priority:high status:open", &urgent));
+        assert!(matches("priority:none", &task));
+        assert!(!matches("priority:high", &task));
+
+        let tagged = Task {
+            tags: ["work".to_string()].into(),
+            ..task.clone()
+        };
+        assert!(matches("tag:work and not tag:home", &tagged));
+        assert!(matches("tag:+Work", &tagged));
+        assert!(!matches("tag:work", &task));
+    }
+
+    #[test]
+    fn rejects_bad_queries() {
+        let now = date("2026-01-14").and_hms_opt(10, 30, 0).unwrap();
+        for query in [
+            "status:maybe",
+            "priority:urgent",
+            "tag:+",
+            "created:soon",
+            "created:>2026-13-01",
+            "owner:me",
+            "(status:open",
+            "status:open)",
+            "label~(",
+            "status:open or",
+            "label~\"unclosed",
+        ] {
+            assert!(Query::parse_at(query, now).is_err(), "{}", query);
+        }
+    }
+}
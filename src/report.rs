@@ -0,0 +1,895 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate};
+use std::collections::{BTreeMap, BTreeSet};
+
+use colored::{Color, Colorize};
+use serde::Serialize;
+
+use crate::dates::{self, local_date};
+use crate::ds::{not_found, Priority, Project, Store, Task, INBOX, INBOX_PREFIX};
+use crate::error::ProgressError;
+use crate::query::{self, Query, SortKey};
+use crate::search::SearchHit;
+use crate::utils::format_timestamp_ago;
+
+/// A view that can be printed for people or as JSON for scripts
+pub trait Report: Serialize {
+    fn print(&self);
+}
+
+pub fn show<R: Report>(report: &R, json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(report).expect("reports serialize")
+        );
+    } else {
+        report.print();
+    }
+}
+
+/// Everything `progress` shows when run without a command
+#[derive(Serialize)]
+pub struct Overview {
+    pub date: String,
+    pub overdue: Vec<Task>,
+    pub due_today: Vec<Task>,
+    pub due_this_week: Vec<Task>,
+    pub today: Vec<Task>,
+    pub carry_over: Vec<Task>,
+    /// How far along the subtasks of each listed task that has some are
+    pub subtasks: Vec<SubtaskProgress>,
+    /// Listed tasks that wait on tasks that are still open
+    pub blocked: Vec<BlockedTask>,
+    pub statistics: Statistics,
+}
+
+#[derive(Serialize)]
+pub struct BlockedTask {
+    pub id: u32,
+    /// The open blockers
+    pub by: Vec<u32>,
+    #[serde(skip)]
+    keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SubtaskProgress {
+    pub id: u32,
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize)]
+pub struct Statistics {
+    pub total: usize,
+    pub completed: usize,
+    pub incomplete: usize,
+    pub created_today: usize,
+    pub done_today: usize,
+    pub done_before_today: usize,
+    pub carry_over: usize,
+    pub overdue: usize,
+    pub earliest_created: Option<String>,
+    pub latest_created: Option<String>,
+    /// Per tag, in alphabetical order
+    pub tags: Vec<TagStatistics>,
+    /// Per project in scope, the inbox first
+    pub projects: Vec<ProjectStatistics>,
+}
+
+#[derive(Serialize)]
+pub struct ProjectStatistics {
+    pub name: String,
+    pub prefix: String,
+    pub archived: bool,
+    pub open: usize,
+    pub done: usize,
+}
+
+#[derive(Serialize)]
+pub struct TagStatistics {
+    pub tag: String,
+    pub open: usize,
+    pub done: usize,
+}
+
+/// The one-line `--minimal` summary
+#[derive(Serialize)]
+pub struct Summary {
+    pub time: String,
+    pub pending_today: usize,
+    pub from_previous_days: usize,
+    pub overdue: usize,
+}
+
+/// Every project, shown by `project list`
+#[derive(Serialize)]
+pub struct ProjectList {
+    pub projects: Vec<ProjectStatistics>,
+}
+
+/// A single task, shown by `--task <id>`
+#[derive(Serialize)]
+pub struct TaskDetail {
+    pub task: Task,
+    pub parent: Option<Task>,
+    pub subtasks: Vec<Task>,
+    /// The tasks it is blocked by, done ones included
+    pub blockers: Vec<Task>,
+}
+
+/// The tasks `list` picked, in the order asked for
+#[derive(Serialize)]
+pub struct TaskList {
+    pub tasks: Vec<Task>,
+}
+
+/// What a date expression resolves to, shown by `date`
+#[derive(Serialize)]
+pub struct DatePreview {
+    pub expression: String,
+    /// RFC 3339, in local time
+    pub date: String,
+    pub timestamp: i64,
+}
+
+/// Tasks matching a `search`, best first
+#[derive(Serialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchHit>,
+}
+
+impl Overview {
+    pub fn build(store: &Store) -> Result<Self, ProgressError> {
+        let today = Local::now().date_naive();
+        let mut tasks_today = store.tasks_today()?;
+        let mut unchecked_tasks_before_today = store.carry_over_tasks()?;
+        // most important first, in the order they were added otherwise
+        tasks_today.sort_by_key(|task| task.priority);
+        unchecked_tasks_before_today.sort_by_key(|task| task.priority);
+
+        // open tasks with a deadline, by local day since that's how they were entered
+        let now = Local::now().timestamp();
+        let week_end = today + Days::new(6 - today.weekday().num_days_from_monday() as u64);
+        let overdue = due_tasks(store, |due, _| due < now);
+        let due_today = due_tasks(store, |due, date| due >= now && date == today);
+        let due_this_week = due_tasks(store, |_, date| date > today && date <= week_end);
+
+        let total_tasks = store.visible_tasks().count();
+        let completed_tasks = store.visible_tasks().filter(|task| task.done).count();
+        let incomplete_tasks = total_tasks - completed_tasks;
+        let done_today = store
+            .visible_tasks()
+            .filter(|task| {
+                if let Some(checked_time) = task.date_checked {
+                    let checked_date = DateTime::from_timestamp(checked_time, 0)
+                        .map(|dt| dt.date_naive())
+                        .unwrap_or(today);
+                    return checked_date == today;
+                }
+                false
+            })
+            .count();
+        let done_before_today = completed_tasks - done_today;
+
+        let earliest_date = store
+            .visible_tasks()
+            .filter_map(|task| {
+                DateTime::from_timestamp(task.date_created, 0).map(|dt| dt.date_naive())
+            })
+            .min();
+        let latest_date = store
+            .visible_tasks()
+            .filter_map(|task| {
+                DateTime::from_timestamp(task.date_created, 0).map(|dt| dt.date_naive())
+            })
+            .max();
+
+        let mut tag_counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for task in store.visible_tasks() {
+            for tag in &task.tags {
+                let (open, done) = tag_counts.entry(tag).or_default();
+                if task.done {
+                    *done += 1;
+                } else {
+                    *open += 1;
+                }
+            }
+        }
+
+        let statistics = Statistics {
+            total: total_tasks,
+            completed: completed_tasks,
+            incomplete: incomplete_tasks,
+            created_today: tasks_today.len(),
+            done_today,
+            done_before_today,
+            carry_over: unchecked_tasks_before_today.len(),
+            overdue: overdue.len(),
+            earliest_created: earliest_date.map(|date| date.to_string()),
+            latest_created: latest_date.map(|date| date.to_string()),
+            tags: tag_counts
+                .into_iter()
+                .map(|(tag, (open, done))| TagStatistics {
+                    tag: tag.to_string(),
+                    open,
+                    done,
+                })
+                .collect(),
+            projects: project_statistics(store, store.visible_projects()),
+        };
+
+        let subtasks = tasks_today
+            .iter()
+            .chain(&unchecked_tasks_before_today)
+            .filter_map(|task| {
+                let (done, total) = store
+                    .subtasks(task.id)
+                    .fold((0, 0), |(done, total), subtask| {
+                        (done + usize::from(subtask.done), total + 1)
+                    });
+                (total > 0).then_some(SubtaskProgress {
+                    id: task.id,
+                    done,
+                    total,
+                })
+            })
+            .collect();
+
+        let blocked = tasks_today
+            .iter()
+            .chain(&unchecked_tasks_before_today)
+            .filter(|task| !task.done)
+            .filter_map(|task| {
+                let (by, keys): (Vec<u32>, Vec<String>) = store
+                    .open_blockers(task)
+                    .map(|blocker| (blocker.id, blocker.key()))
+                    .unzip();
+                (!by.is_empty()).then_some(BlockedTask {
+                    id: task.id,
+                    by,
+                    keys,
+                })
+            })
+            .collect();
+
+        return Ok(Overview {
+            date: today.to_string(),
+            overdue,
+            due_today,
+            due_this_week,
+            today: tasks_today,
+            carry_over: unchecked_tasks_before_today,
+            subtasks,
+            blocked,
+            statistics,
+        });
+    }
+}
+
+impl Overview {
+    /// ` n/m done` after a task with subtasks, nothing for other tasks
+    fn progress_badge(&self, id: u32) -> String {
+        return self
+            .subtasks
+            .iter()
+            .find(|progress| progress.id == id)
+            .map(|progress| format!(" {}", subtask_badge(progress.done, progress.total)))
+            .unwrap_or_default();
+    }
+
+    /// ` blocked by TSK-3, TSK-4` after a blocked task, none for other tasks
+    fn blockers(&self, id: u32) -> Option<String> {
+        let blocked = self.blocked.iter().find(|blocked| blocked.id == id)?;
+        let line = format!(" blocked by {}", blocked.keys.join(", "));
+        return Some(line.dimmed().to_string());
+    }
+}
+
+impl Report for Overview {
+    fn print(&self) {
+        let sections = [
+            ("Overdue:".red().bold(), &self.overdue),
+            ("Due today:".yellow().bold(), &self.due_today),
+            ("Due this week:".cyan().bold(), &self.due_this_week),
+        ];
+        for (title, tasks) in sections {
+            if tasks.is_empty() {
+                continue;
+            }
+            println!("{}", title);
+            for task in tasks {
+                println!(
+                    "{} {}{}{} {}",
+                    format!("{} - [-]", task.key()).red(),
+                    priority_marker(task.priority),
+                    task.label,
+                    tag_chips(task),
+                    format!(
+                        "(due {})",
+                        format_timestamp_ago(task.date_due.unwrap_or_default())
+                    )
+                    .dimmed()
+                );
+            }
+            println!();
+        }
+
+        if self.today.is_empty() {
+            // there's no task created today
+            if self.carry_over.is_empty() {
+                // there's o tsk created in the past that needs to be do today
+                println!("{}", "No tasks for today".green().bold());
+            }
+        } else {
+            println!("{}", "Tasks for Today:".green().bold());
+            for (heading, depth, task) in by_project(&self.today, &self.statistics.projects) {
+                if let Some(heading) = heading {
+                    println!("{}", heading);
+                }
+                let blockers = self.blockers(task.id);
+                println!(
+                    "{}{} {}{}{}{}{}",
+                    "  ".repeat(depth),
+                    if task.done {
+                        format!("{} - [x]", task.key()).green()
+                    } else if blockers.is_some() {
+                        format!("{} - [-]", task.key()).dimmed()
+                    } else {
+                        format!("{} - [-]", task.key()).red()
+                    },
+                    priority_marker(task.priority),
+                    if task.done {
+                        task.label.strikethrough()
+                    } else if blockers.is_some() {
+                        task.label.dimmed()
+                    } else {
+                        task.label.normal()
+                    },
+                    tag_chips(task),
+                    self.progress_badge(task.id),
+                    blockers.unwrap_or_default()
+                );
+            }
+            println!();
+        }
+
+        if !self.carry_over.is_empty() {
+            println!("{}", "Carry-over tasks:".yellow().bold());
+            for (heading, depth, task) in by_project(&self.carry_over, &self.statistics.projects) {
+                if let Some(heading) = heading {
+                    println!("{}", heading);
+                }
+                let leading = format!(
+                    "{} ({})",
+                    task.key(),
+                    format_timestamp_ago(task.date_created)
+                );
+                let blockers = self.blockers(task.id);
+                println!(
+                    "{}{} - [{}] {}{}{}{}{}",
+                    "  ".repeat(depth),
+                    leading.on_truecolor(100, 100, 100),
+                    if task.done { "x" } else { " " },
+                    priority_marker(task.priority),
+                    if blockers.is_some() {
+                        task.label.dimmed()
+                    } else {
+                        task.label.normal()
+                    },
+                    tag_chips(task),
+                    self.progress_badge(task.id),
+                    blockers.unwrap_or_default()
+                );
+            }
+        }
+
+        let statistics = &self.statistics;
+        println!("{}", "\nStatistics:".green().bold());
+        println!("- Total tasks: {}", statistics.total);
+        println!("- Completed tasks: {}", statistics.completed);
+        println!("- Incomplete tasks: {}", statistics.incomplete);
+        println!("- Tasks created today: {}", statistics.created_today);
+        println!("- Tasks marked as done today: {}", statistics.done_today);
+        println!(
+            "- Tasks marked as done before today: {}",
+            statistics.done_before_today
+        );
+        println!(
+            "- Unchecked tasks from before today: {}",
+            statistics.carry_over
+        );
+        println!("- Overdue tasks: {}", statistics.overdue);
+
+        if let Some(earliest) = &statistics.earliest_created {
+            println!("- Earliest task creation date: {}", earliest);
+        }
+        if let Some(latest) = &statistics.latest_created {
+            println!("- Latest task creation date: {}", latest);
+        }
+        if statistics.projects.len() > 1 {
+            for project in &statistics.projects {
+                println!(
+                    "- {} ({}): {} open, {} done",
+                    project.name.bold(),
+                    project.prefix,
+                    project.open,
+                    project.done
+                );
+            }
+        }
+        for tag in &statistics.tags {
+            println!(
+                "- Tagged {}: {} open, {} done",
+                chip(&tag.tag),
+                tag.open,
+                tag.done
+            );
+        }
+
+        println!("Use --help to see more.");
+    }
+}
+
+impl Summary {
+    pub fn build(store: &Store) -> Self {
+        let today = Local::now().date_naive();
+
+        // pending tasks (including unchecked tasks from previous days)
+        let pending_tasks_today = store
+            .visible_tasks()
+            .filter(|task| {
+                let task_date = DateTime::from_timestamp(task.date_created, 0)
+                    .map(|dt| dt.date_naive())
+                    .unwrap_or(today);
+
+                // task is either from today or unchecked (not marked as done)
+                (task_date == today || task.date_checked.is_none()) && !task.done
+            })
+            .count();
+
+        // unchecked tasks from previous days
+        let pending_tasks_previous_days = store
+            .visible_tasks()
+            .filter(|task| {
+                let task_date = DateTime::from_timestamp(task.date_created, 0)
+                    .map(|dt| dt.date_naive())
+                    .unwrap_or(today);
+
+                // task is from a previous day and unchecked
+                task_date < today && task.date_checked.is_none() && !task.done
+            })
+            .count();
+
+        let now = Local::now().timestamp();
+        let overdue_tasks = store
+            .visible_tasks()
+            .filter(|task| !task.done && task.date_due.is_some_and(|due| due < now))
+            .count();
+
+        return Summary {
+            time: Local::now().format("%H:%M").to_string(),
+            pending_today: pending_tasks_today,
+            from_previous_days: pending_tasks_previous_days,
+            overdue: overdue_tasks,
+        };
+    }
+}
+
+impl Report for Summary {
+    fn print(&self) {
+        if self.pending_today + self.from_previous_days == 0 {
+            println!(
+                "📅 {} You gotta lockin! create a task! see --help",
+                format!("[{}]", self.time).green(),
+            );
+        } else {
+            let overdue = if self.overdue > 0 {
+                format!(", {} overdue", self.overdue.to_string().red().bold())
+            } else {
+                String::new()
+            };
+            println!(
+                "📅 {} You have {} pending task(s) for today, {} from previous days{}",
+                format!("[{}]", self.time).green(),
+                self.pending_today.to_string().yellow().bold(),
+                self.from_previous_days.to_string().red().bold(),
+                overdue
+            )
+        };
+    }
+}
+
+impl ProjectList {
+    pub fn build(store: &Store) -> Self {
+        let mut projects = vec![None];
+        projects.extend(store.metadata.projects.iter().map(Some));
+        return ProjectList {
+            projects: project_statistics(store, projects),
+        };
+    }
+}
+
+impl Report for ProjectList {
+    fn print(&self) {
+        for project in &self.projects {
+            let line = format!(
+                "{:<8} {} ({} open, {} done)",
+                project.prefix, project.name, project.open, project.done
+            );
+            if project.archived {
+                println!("{} {}", line.dimmed(), "archived".dimmed());
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+impl TaskDetail {
+    pub fn build(store: &Store, id: u32) -> Result<Self, ProgressError> {
+        let task = store
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .ok_or_else(|| not_found(id))?;
+
+        let parent = task
+            .parent
+            .and_then(|parent| store.tasks.iter().find(|other| other.id == parent))
+            .cloned();
+        let subtasks = store.subtasks(id).cloned().collect();
+        let blockers = store
+            .tasks
+            .iter()
+            .filter(|blocker| task.blocked_by.contains(&blocker.id))
+            .cloned()
+            .collect();
+
+        return Ok(TaskDetail {
+            task: task.clone(),
+            parent,
+            subtasks,
+            blockers,
+        });
+    }
+}
+
+impl Report for TaskDetail {
+    fn print(&self) {
+        let task = &self.task;
+        println!(
+            "{} · {}",
+            if task.done {
+                "[x]".green()
+            } else {
+                "[-]".red()
+            },
+            task.key()
+        );
+        println!("----------------------");
+        println!("{}", task.label);
+        if let Some(parent) = &self.parent {
+            println!("Subtask of {} {}", parent.key(), parent.label);
+        }
+        for blocker in &self.blockers {
+            let line = format!("Blocked by {} {}", blocker.key(), blocker.label);
+            println!(
+                "{}",
+                if blocker.done {
+                    format!("{} (done)", line).dimmed()
+                } else {
+                    line.yellow()
+                }
+            );
+        }
+        if !task.tags.is_empty() {
+            println!("Tags:{}", tag_chips(task));
+        }
+        if task.priority != Priority::None {
+            println!(
+                "Priority: {}{}",
+                priority_marker(task.priority),
+                task.priority.name()
+            );
+        }
+        if let Some(date_due) = task.date_due {
+            let when = DateTime::from_timestamp(date_due, 0)
+                .map(|date| {
+                    date.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let overdue = !task.done && date_due < Local::now().timestamp();
+            let line = format!("Due {} ({})", when, format_timestamp_ago(date_due));
+            println!("{}", if overdue { line.red() } else { line.normal() });
+        }
+        println!("Created ({})", format_timestamp_ago(task.date_created));
+
+        if let Some(date_checked) = task.date_checked {
+            println!("Finished ({})", format_timestamp_ago(date_checked))
+        }
+
+        if !self.subtasks.is_empty() {
+            let done = self.subtasks.iter().filter(|subtask| subtask.done).count();
+            println!("\nSubtasks ({}):", subtask_badge(done, self.subtasks.len()));
+            for subtask in &self.subtasks {
+                println!(
+                    "  {} {} {}",
+                    if subtask.done {
+                        "[x]".green()
+                    } else {
+                        "[-]".red()
+                    },
+                    subtask.key(),
+                    subtask.label
+                );
+            }
+        }
+    }
+}
+
+impl TaskList {
+    pub fn build(
+        store: &Store,
+        query: &Query,
+        sort: SortKey,
+        reverse: bool,
+        limit: Option<usize>,
+    ) -> Self {
+        let mut tasks: Vec<Task> = store
+            .visible_tasks()
+            .filter(|task| query.matches(task))
+            .cloned()
+            .collect();
+
+        query::sort(&mut tasks, sort);
+        if reverse {
+            tasks.reverse();
+        }
+        if let Some(limit) = limit {
+            tasks.truncate(limit);
+        }
+        return TaskList { tasks };
+    }
+}
+
+impl Report for TaskList {
+    fn print(&self) {
+        if self.tasks.is_empty() {
+            println!("{}", "No matching tasks".green().bold());
+            return;
+        }
+
+        for task in &self.tasks {
+            println!(
+                "{} {}{}{} {}",
+                if task.done {
+                    format!("{} - [x]", task.key()).green()
+                } else {
+                    format!("{} - [-]", task.key()).red()
+                },
+                priority_marker(task.priority),
+                task.label,
+                tag_chips(task),
+                format!("({})", format_timestamp_ago(task.date_created)).dimmed()
+            );
+        }
+    }
+}
+
+impl DatePreview {
+    pub fn build(expression: &str) -> Result<Self, ProgressError> {
+        let date = dates::parse(expression)?;
+        return Ok(DatePreview {
+            expression: expression.to_string(),
+            date: date.to_rfc3339(),
+            timestamp: date.timestamp(),
+        });
+    }
+}
+
+impl Report for DatePreview {
+    fn print(&self) {
+        let date = DateTime::parse_from_rfc3339(&self.date).expect("built from a date");
+        println!(
+            "{} {}",
+            date.format("%a %Y-%m-%d %H:%M").to_string().green().bold(),
+            format!("({})", format_timestamp_ago(self.timestamp)).dimmed()
+        );
+    }
+}
+
+impl SearchResults {
+    pub fn build(store: &Store, terms: &str, limit: usize) -> Self {
+        let mut results = store.search(terms);
+        results.truncate(limit);
+        return SearchResults { results };
+    }
+}
+
+impl Report for SearchResults {
+    fn print(&self) {
+        if self.results.is_empty() {
+            println!("{}", "No matching tasks".green().bold());
+            return;
+        }
+
+        for hit in &self.results {
+            let task = &hit.task;
+            // the label with every matched word highlighted
+            let mut label = String::new();
+            let mut position = 0;
+            for range in &hit.matches {
+                label.push_str(&task.label[position..range.start]);
+                label.push_str(&task.label[range.clone()].yellow().bold().to_string());
+                position = range.end;
+            }
+            label.push_str(&task.label[position..]);
+
+            println!(
+                "{} {}{}{} {}",
+                if task.done {
+                    format!("{} - [x]", task.key()).green()
+                } else {
+                    format!("{} - [-]", task.key()).red()
+                },
+                priority_marker(task.priority),
+                label,
+                tag_chips(task),
+                format!("({})", format_timestamp_ago(task.date_created)).dimmed()
+            );
+        }
+    }
+}
+
+/// Open tasks whose due date (and its local day) passes `keep`, soonest first
+fn due_tasks(store: &Store, keep: impl Fn(i64, NaiveDate) -> bool) -> Vec<Task> {
+    let mut tasks: Vec<Task> = store
+        .visible_tasks()
+        .filter(|task| match (task.done, task.date_due) {
+            (false, Some(due)) => local_date(due).is_some_and(|date| keep(due, date)),
+            _ => false,
+        })
+        .cloned()
+        .collect();
+    tasks.sort_by_key(|task| (task.date_due, task.priority, task.id));
+    return tasks;
+}
+
+/// Open and done counts for each of `projects`, `None` being the inbox
+fn project_statistics(store: &Store, projects: Vec<Option<&Project>>) -> Vec<ProjectStatistics> {
+    return projects
+        .into_iter()
+        .map(|project| {
+            let prefix = project.map_or(INBOX_PREFIX, |project| &project.prefix);
+            let tasks = store.tasks.iter().filter(|task| task.prefix() == prefix);
+            let done = tasks.clone().filter(|task| task.done).count();
+            ProjectStatistics {
+                name: project.map_or(INBOX, |project| &project.name).to_string(),
+                prefix: prefix.to_string(),
+                archived: project.is_some_and(|project| project.archived),
+                open: tasks.count() - done,
+                done,
+            }
+        })
+        .collect();
+}
+
+/// The tasks grouped by project, in the order of `projects`, each group led
+/// by the project's name, with subtasks nested under their parent. Tasks from
+/// a single project get no headings.
+fn by_project<'a>(
+    tasks: &'a [Task],
+    projects: &[ProjectStatistics],
+) -> Vec<(Option<String>, usize, &'a Task)> {
+    let mut groups: Vec<(&ProjectStatistics, Vec<&Task>)> = projects
+        .iter()
+        .map(|project| {
+            let tasks = tasks
+                .iter()
+                .filter(|task| task.prefix() == project.prefix)
+                .collect();
+            (project, tasks)
+        })
+        .collect();
+    groups.retain(|(_, tasks)| !tasks.is_empty());
+
+    let grouped: usize = groups.iter().map(|(_, tasks)| tasks.len()).sum();
+    if groups.len() <= 1 || grouped != tasks.len() {
+        return nest(tasks.iter().collect())
+            .into_iter()
+            .map(|(depth, task)| (None, depth, task))
+            .collect();
+    }
+    let mut rows = vec![];
+    for (project, tasks) in groups {
+        let heading = project.name.bold().underline().to_string();
+        for (index, (depth, task)) in nest(tasks).into_iter().enumerate() {
+            rows.push(((index == 0).then(|| heading.clone()), depth, task));
+        }
+    }
+    return rows;
+}
+
+/// The tasks in order, each followed by its listed subtasks one level deeper.
+/// Tasks whose parent isn't listed start at the top level.
+fn nest(tasks: Vec<&Task>) -> Vec<(usize, &Task)> {
+    fn visit<'a>(
+        task: &'a Task,
+        depth: usize,
+        tasks: &[&'a Task],
+        placed: &mut BTreeSet<u32>,
+        rows: &mut Vec<(usize, &'a Task)>,
+    ) {
+        if !placed.insert(task.id) {
+            return;
+        }
+        rows.push((depth, task));
+        for child in tasks.iter().filter(|child| child.parent == Some(task.id)) {
+            visit(child, depth + 1, tasks, placed, rows);
+        }
+    }
+
+    let listed: BTreeSet<u32> = tasks.iter().map(|task| task.id).collect();
+    let mut placed = BTreeSet::new();
+    let mut rows = vec![];
+    for task in &tasks {
+        let is_root = task.parent.is_none_or(|parent| !listed.contains(&parent));
+        if is_root {
+            visit(task, 0, &tasks, &mut placed, &mut rows);
+        }
+    }
+    // parents that point at each other, which doctor would untangle
+    for task in &tasks {
+        visit(task, 0, &tasks, &mut placed, &mut rows);
+    }
+    return rows;
+}
+
+/// `n/m done`, green once every subtask is done
+fn subtask_badge(done: usize, total: usize) -> String {
+    let badge = format!("{}/{} done", done, total);
+    return if done == total {
+        badge.green().to_string()
+    } else {
+        badge.dimmed().to_string()
+    };
+}
+
+/// `!!!`, `!!` or `!` in front of a label, nothing for tasks without a priority
+fn priority_marker(priority: Priority) -> String {
+    let marker = match priority {
+        Priority::High => "!!!".red().bold(),
+        Priority::Medium => "!!".yellow().bold(),
+        Priority::Low => "!".blue(),
+        Priority::None => return String::new(),
+    };
+    return format!("{} ", marker);
+}
+
+/// Each of the task's tags as a chip, with a space in front of each
+fn tag_chips(task: &Task) -> String {
+    return task
+        .tags
+        .iter()
+        .map(|tag| format!(" {}", chip(tag)))
+        .collect();
+}
+
+/// A tag on a colored background, the same color every time for one tag
+fn chip(tag: &str) -> String {
+    const COLORS: [Color; 5] = [
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Green,
+        Color::Yellow,
+    ];
+    let hash = tag.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as usize)
+    });
+    let color = COLORS[hash % COLORS.len()];
+    return format!(" {} ", tag).black().on_color(color).to_string();
+}
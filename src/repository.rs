@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use crate::ds::{Metadata, Task};
+use crate::error::ProgressError;
+use crate::text_repository::TextRepository;
+
+/// Which tasks a query returns. Unset fields don't filter.
+#[derive(Default)]
+pub struct TaskQuery {
+    pub done: Option<bool>,
+    /// Created at or after this timestamp
+    pub created_from: Option<i64>,
+    /// Created before this timestamp
+    pub created_until: Option<i64>,
+}
+
+impl TaskQuery {
+    pub fn matches(&self, task: &Task) -> bool {
+        if let Some(done) = self.done {
+            if task.done != done {
+                return false;
+            }
+        }
+        if let Some(from) = self.created_from {
+            if task.date_created < from {
+                return false;
+            }
+        }
+        if let Some(until) = self.created_until {
+            if task.date_created >= until {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+/// Where a store keeps its tasks. Changes made through `insert`, `update` and
+/// `delete` only have to be durable once `commit` returns.
+pub trait TaskRepository {
+    /// Reads the metadata and every task
+    fn load(&mut self) -> Result<(Metadata, Vec<Task>), ProgressError>;
+    fn insert(&mut self, task: &Task) -> Result<(), ProgressError>;
+    fn update(&mut self, task: &Task) -> Result<(), ProgressError>;
+    fn delete(&mut self, task: &Task) -> Result<(), ProgressError>;
+    /// Tasks matching `query`, in id order
+    fn query(&self, query: &TaskQuery) -> Result<Vec<Task>, ProgressError>;
+    /// Persists every change since the last commit along with `metadata`
+    fn commit(&mut self, metadata: &Metadata) -> Result<(), ProgressError>;
+}
+
+/// `.db`, `.sqlite` and `.sqlite3` stores use SQLite, anything else the text format
+pub fn is_sqlite(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    return matches!(extension, "db" | "sqlite" | "sqlite3");
+}
+
+/// Picks the backend from the store's file extension.
+/// `writable` allows upgrading an old store while opening it.
+pub fn open_repository(
+    path: &Path,
+    writable: bool,
+) -> Result<Box<dyn TaskRepository>, ProgressError> {
+    if is_sqlite(path) {
+        return open_sqlite(path, writable);
+    }
+    return Ok(Box::new(TextRepository::new(path, writable)));
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &Path, writable: bool) -> Result<Box<dyn TaskRepository>, ProgressError> {
+    let repository = crate::sqlite_repository::SqliteRepository::open(path, writable)?;
+    return Ok(Box::new(repository));
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(path: &Path, _writable: bool) -> Result<Box<dyn TaskRepository>, ProgressError> {
+    return Err(ProgressError::Usage(format!(
+        "{} is a SQLite store, but this build has no SQLite support (rebuild with --features sqlite)",
+        path.display()
+    )));
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use serde::Serialize;
+
+use crate::ds::Task;
+
+/// Stores with at least this many tasks get an index when they're opened
+pub const INDEX_THRESHOLD: usize = 500;
+
+/// Maps every word in a label to the tasks using it, so a search only looks
+/// at the vocabulary instead of every label
+#[derive(Default)]
+pub struct SearchIndex {
+    postings: BTreeMap<String, BTreeSet<u32>>,
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub task: Task,
+    pub score: f64,
+    /// Byte ranges of the label that matched
+    pub matches: Vec<Range<usize>>,
+}
+
+impl SearchIndex {
+    pub fn build(tasks: &[Task]) -> Self {
+        let mut index = SearchIndex::default();
+        for task in tasks {
+            index.insert(task);
+        }
+        return index;
+    }
+
+    pub fn insert(&mut self, task: &Task) {
+        for (_, word) in words(&task.label) {
+            self.postings.entry(word).or_default().insert(task.id);
+        }
+    }
+
+    pub fn remove(&mut self, task: &Task) {
+        for (_, word) in words(&task.label) {
+            if let Some(ids) = self.postings.get_mut(&word) {
+                ids.remove(&task.id);
+                if ids.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Ids of the tasks with a word that `term` matches at all
+    fn candidates(&self, terms: &[String]) -> BTreeSet<u32> {
+        let mut ids = BTreeSet::new();
+        for (word, postings) in &self.postings {
+            if terms.iter().any(|term| score(term, word) > 0.0) {
+                ids.extend(postings);
+            }
+        }
+        return ids;
+    }
+}
+
+/// Ranks tasks by how well their label matches `query`, best first. Every
+/// term adds the score of the word it matches best, so tasks matching more
+/// terms, and matching them more closely, come first.
+pub fn search(tasks: &[Task], index: Option<&SearchIndex>, query: &str) -> Vec<SearchHit> {
+    let terms: Vec<String> = words(query).into_iter().map(|(_, word)| word).collect();
+    if terms.is_empty() {
+        return vec![];
+    }
+
+    let candidates = index.map(|index| index.candidates(&terms));
+    let mut hits: Vec<SearchHit> = tasks
+        .iter()
+        .filter(|task| match &candidates {
+            Some(ids) => ids.contains(&task.id),
+            None => true,
+        })
+        .filter_map(|task| rank(task, &terms))
+        .collect();
+
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.task.date_created.cmp(&a.task.date_created))
+            .then(a.task.id.cmp(&b.task.id))
+    });
+    return hits;
+}
+
+fn rank(task: &Task, terms: &[String]) -> Option<SearchHit> {
+    let label_words = words(&task.label);
+    let mut total = 0.0;
+    let mut matches = vec![];
+
+    for term in terms {
+        let best = label_words
+            .iter()
+            .map(|(range, word)| (score(term, word), range))
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((score, range)) = best {
+            if score > 0.0 {
+                total += score;
+                matches.push(range.clone());
+            }
+        }
+    }
+
+    if total == 0.0 {
+        return None;
+    }
+    matches.sort_by_key(|range| range.start);
+    matches.dedup();
+    return Some(SearchHit {
+        task: task.clone(),
+        score: total,
+        matches,
+    });
+}
+
+/// How well `term` matches `word`, from 1 (the same word) down to 0 (no match)
+fn score(term: &str, word: &str) -> f64 {
+    if word == term {
+        return 1.0;
+    }
+    if word.starts_with(term) {
+        return 0.8;
+    }
+    let length = term.chars().count();
+    if length >= 3 && word.contains(term) {
+        return 0.6;
+    }
+
+    // other forms of the same word, like invoice and invoicing
+    let shared = term
+        .chars()
+        .zip(word.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if length >= 4 && shared >= (length - 2).max(4) {
+        return 0.5;
+    }
+
+    // typos: one edit in words of 4 letters or more, two from 8
+    let allowed = match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if allowed > 0 {
+        let distance = edit_distance(term, word);
+        if distance <= allowed {
+            return 0.45 - 0.05 * distance as f64;
+        }
+    }
+    return 0.0;
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
+
+/// Lowercased runs of letters and digits, with where they are in `text`
+fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = vec![];
+    let mut start = None;
+
+    for (index, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(from)) => {
+                words.push((from..index, text[from..index].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        words.push((from..text.len(), text[from..].to_lowercase()));
+    }
+    return words;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(labels: &[&str]) -> Vec<Task> {
+        return labels
+            .iter()
+            .enumerate()
+            .map(|(id, label)| Task {
+                id: id as u32,
+                label: label.to_string(),
+                ..Task::default()
+            })
+            .collect();
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<u32> {
+        return hits.iter().map(|hit| hit.task.id).collect();
+    }
+
+    #[test]
+    fn ranks_closer_matches_first() {
+        let tasks = tasks(&[
+            "Call the bank",
+            "Send the invoice to Acme",
+            "Invoicing tool research",
+            "Pay invoce for hosting",
+        ]);
+        let hits = search(&tasks, None, "invoice");
+        assert_eq!(ids(&hits), vec![1, 2, 3]);
+        assert_eq!(hits[0].matches, vec![9..16]);
+    }
+
+    #[test]
+    fn index_finds_the_same_tasks() {
+        let mut tasks = tasks(&["Send the invoice", "Renew passport", "Invoice Acme again"]);
+        let mut index = SearchIndex::build(&tasks);
+        index.remove(&tasks[2]);
+        tasks[2].label = "Email Acme".to_string();
+        index.insert(&tasks[2]);
+
+        for query in ["invoice", "acme", "pasport", "xyz"] {
+            assert_eq!(
+                ids(&search(&tasks, Some(&index), query)),
+                ids(&search(&tasks, None, query)),
+                "{}",
+                query
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use clap::Parser;
+use colored::Colorize;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::cli::Cli;
+use crate::commands;
+use crate::ds::{sibling_path, Store};
+use crate::error::ProgressError;
+use crate::lock::LockMode;
+use crate::report::{Report, Summary};
+
+/// Reads commands until `exit` or end of input, running each one against the
+/// already open store. Commands that change tasks save before the next prompt.
+/// The store is only locked while a command runs.
+/// `project` is the scope commands get unless they pass their own `--project`.
+pub fn run(
+    store: &mut Store,
+    store_path: &Path,
+    project: Option<&str>,
+) -> Result<(), ProgressError> {
+    // nothing is locked while waiting at the prompt
+    store.unlock();
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    let history = sibling_path(store_path, "history");
+    // there's no history the first time
+    let _ = editor.load_history(&history);
+
+    println!("Type a command like `add \"Buy groceries\"`, `help` for the list, `exit` to leave.");
+    loop {
+        Summary::build(store).print();
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        if let Err(e) = run_line(store, line, project) {
+            println!("{}", e.to_string().red());
+        }
+    }
+
+    editor.save_history(&history).map_err(readline_error)?;
+    return Ok(());
+}
+
+/// Runs one line the way the command line would, `progress` in front optional
+fn run_line(store: &mut Store, line: &str, project: Option<&str>) -> Result<(), ProgressError> {
+    let mut words =
+        shlex::split(line).ok_or_else(|| ProgressError::Usage("Unbalanced quotes".to_string()))?;
+    if words.first().map(|word| word.as_str()) != Some("progress") {
+        words.insert(0, "progress".to_string());
+    }
+
+    let cli = match Cli::try_parse_from(words) {
+        Ok(cli) => cli,
+        Err(e) => {
+            // help and usage errors print the same as they do outside
+            let _ = e.print();
+            return Ok(());
+        }
+    };
+    if cli.store.is_some() || cli.lock_timeout.is_some() {
+        return Err(ProgressError::Usage(
+            "The shell keeps using the store it was started with".to_string(),
+        ));
+    }
+    let scope = cli.project.as_deref().or(project);
+    let mode = match &cli.command {
+        Some(command) if command.changes_store() => LockMode::Exclusive,
+        _ => LockMode::Shared,
+    };
+    // reads the store again, so it has what other processes saved meanwhile
+    return store.locked(mode, |store| {
+        store.set_scope(scope)?;
+        commands::execute(store, cli.command, cli.json)
+    });
+}
+
+fn readline_error(error: ReadlineError) -> ProgressError {
+    match error {
+        ReadlineError::Io(e) => ProgressError::Io(e),
+        e => ProgressError::Io(std::io::Error::other(e)),
+    }
+}
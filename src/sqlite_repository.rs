@@ -0,0 +1,403 @@
+use std::{fs, io, path::Path};
+
+use rusqlite::backup::Progress;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{
+    params, params_from_iter, Connection, DatabaseName, OptionalExtension, Row, TransactionBehavior,
+};
+
+use crate::ds::{sibling_path, split_ids, Metadata, Priority, Project, Task};
+use crate::error::ProgressError;
+use crate::repository::{TaskQuery, TaskRepository};
+
+/// Schema written by this build, kept in `PRAGMA user_version`
+const SCHEMA_VERSION: u32 = 7;
+
+/// `SCHEMA_MIGRATIONS[n]` upgrades a version `n` database to `n + 1`
+const SCHEMA_MIGRATIONS: [&str; SCHEMA_VERSION as usize] = [
+    "
+    CREATE TABLE metadata (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+    CREATE TABLE tasks (
+        id INTEGER PRIMARY KEY,
+        done INTEGER NOT NULL,
+        label TEXT NOT NULL,
+        date_created INTEGER NOT NULL,
+        date_checked INTEGER
+    );
+    CREATE INDEX tasks_by_date_created ON tasks (date_created);
+    CREATE INDEX tasks_by_done ON tasks (done);
+",
+    "
+    ALTER TABLE tasks ADD COLUMN priority TEXT NOT NULL DEFAULT 'none';
+",
+    "
+    ALTER TABLE tasks ADD COLUMN date_due INTEGER;
+    CREATE INDEX tasks_by_date_due ON tasks (date_due);
+",
+    "
+    ALTER TABLE tasks ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+",
+    "
+    ALTER TABLE tasks ADD COLUMN project TEXT;
+    CREATE TABLE projects (
+        prefix TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        archived INTEGER NOT NULL DEFAULT 0
+    );
+",
+    "
+    ALTER TABLE tasks ADD COLUMN parent INTEGER;
+    CREATE INDEX tasks_by_parent ON tasks (parent);
+",
+    "
+    ALTER TABLE tasks ADD COLUMN blocked_by TEXT NOT NULL DEFAULT '';
+",
+];
+
+const TASK_COLUMNS: &str =
+    "id, done, label, date_created, date_checked, priority, date_due, tags, project, parent, blocked_by";
+
+impl From<rusqlite::Error> for ProgressError {
+    fn from(error: rusqlite::Error) -> Self {
+        ProgressError::Io(io::Error::other(error))
+    }
+}
+
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        return Ok(ToSqlOutput::from(self.name()));
+    }
+}
+
+impl FromSql for Priority {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let name = value.as_str()?;
+        return Priority::from_name(name)
+            .ok_or_else(|| FromSqlError::Other(format!("unknown priority \"{}\"", name).into()));
+    }
+}
+
+/// Keeps tasks in a SQLite database, so saves only write the rows that
+/// changed. `Store` still loads every task when it opens.
+pub struct SqliteRepository {
+    connection: Connection,
+}
+
+impl SqliteRepository {
+    pub fn open(path: &Path, writable: bool) -> Result<Self, ProgressError> {
+        // don't leave an empty database behind for a read-only command
+        if !writable && !path.exists() {
+            let connection = Connection::open_in_memory()?;
+            upgrade(&connection, 0)?;
+            return Ok(SqliteRepository { connection });
+        }
+
+        let mut repository = SqliteRepository {
+            connection: Connection::open(path)?,
+        };
+        repository.migrate(path, writable)?;
+        return Ok(repository);
+    }
+
+    /// Brings the schema up to date. Read-only commands upgrade a copy in
+    /// memory and leave the file alone; otherwise the file is upgraded in one
+    /// transaction, after keeping the original as `progress.db.v<n>.bak`.
+    fn migrate(&mut self, path: &Path, writable: bool) -> Result<(), ProgressError> {
+        let version = schema_version(&self.connection)?;
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        if !writable {
+            let mut copy = Connection::open_in_memory()?;
+            copy.restore(DatabaseName::Main, path, None::<fn(Progress)>)?;
+            upgrade(&copy, version)?;
+            self.connection = copy;
+            return Ok(());
+        }
+
+        let transaction = self
+            .connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // another process may have upgraded it while we waited for the lock
+        let version = schema_version(&transaction)?;
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+        // a new database has nothing to keep. Otherwise nothing is written
+        // yet and no one else can write, so the file is whole
+        if version > 0 {
+            fs::copy(path, sibling_path(path, &format!("v{}.bak", version)))?;
+        }
+        upgrade(&transaction, version)?;
+        transaction.commit()?;
+        return Ok(());
+    }
+
+    /// Changes are grouped into one transaction that `commit` ends
+    fn begin(&self) -> Result<(), ProgressError> {
+        if self.connection.is_autocommit() {
+            self.connection.execute_batch("BEGIN IMMEDIATE")?;
+        }
+        return Ok(());
+    }
+
+    fn read_task(row: &Row) -> rusqlite::Result<Task> {
+        return Ok(Task {
+            id: row.get(0)?,
+            done: row.get(1)?,
+            label: row.get(2)?,
+            date_created: row.get(3)?,
+            date_checked: row.get(4)?,
+            priority: row.get(5)?,
+            date_due: row.get(6)?,
+            tags: row
+                .get::<_, String>(7)?
+                .split_whitespace()
+                .map(|tag| tag.to_string())
+                .collect(),
+            project: row.get(8)?,
+            parent: row.get(9)?,
+            blocked_by: split_ids(&row.get::<_, String>(10)?)
+                .ok_or_else(|| FromSqlError::Other("invalid blocking task ids".into()))?,
+        });
+    }
+}
+
+/// The schema version the database says it has, refusing newer ones
+fn schema_version(connection: &Connection) -> Result<u32, ProgressError> {
+    let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(ProgressError::UnsupportedVersion {
+            found: version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    return Ok(version);
+}
+
+/// Runs the migrations from `version` on, the caller decides about transactions
+fn upgrade(connection: &Connection, version: u32) -> Result<(), ProgressError> {
+    for migration in SCHEMA_MIGRATIONS.iter().skip(version as usize) {
+        connection.execute_batch(migration)?;
+    }
+    connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    return Ok(());
+}
+
+impl TaskRepository for SqliteRepository {
+    fn load(&mut self) -> Result<(Metadata, Vec<Task>), ProgressError> {
+        let last_task_id: Option<u32> = self
+            .connection
+            .query_row(
+                "SELECT value FROM metadata WHERE key = 'last_task_id'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        // in the order they were created
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, prefix, archived FROM projects ORDER BY rowid")?;
+        let projects = statement
+            .query_map([], |row| {
+                Ok(Project {
+                    name: row.get(0)?,
+                    prefix: row.get(1)?,
+                    archived: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Project>>>()?;
+        drop(statement);
+
+        let metadata = Metadata {
+            last_task_id: last_task_id.unwrap_or(0),
+            projects,
+        };
+        let tasks = self.query(&TaskQuery::default())?;
+        return Ok((metadata, tasks));
+    }
+
+    fn insert(&mut self, task: &Task) -> Result<(), ProgressError> {
+        self.begin()?;
+        self.connection.execute(
+            &format!(
+                "INSERT INTO tasks ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                TASK_COLUMNS
+            ),
+            params![
+                task.id,
+                task.done,
+                task.label,
+                task.date_created,
+                task.date_checked,
+                task.priority,
+                task.date_due,
+                task.joined_tags(),
+                task.project,
+                task.parent,
+                task.joined_blocked_by()
+            ],
+        )?;
+        return Ok(());
+    }
+
+    fn update(&mut self, task: &Task) -> Result<(), ProgressError> {
+        self.begin()?;
+        let updated = self.connection.execute(
+            "UPDATE tasks SET done = ?2, label = ?3, date_created = ?4, date_checked = ?5, priority = ?6, date_due = ?7, tags = ?8,
+             project = ?9, parent = ?10, blocked_by = ?11 WHERE id = ?1",
+            params![
+                task.id,
+                task.done,
+                task.label,
+                task.date_created,
+                task.date_checked,
+                task.priority,
+                task.date_due,
+                task.joined_tags(),
+                task.project,
+                task.parent,
+                task.joined_blocked_by()
+            ],
+        )?;
+        if updated == 0 {
+            return Err(ProgressError::NotFound(task.key()));
+        }
+        return Ok(());
+    }
+
+    fn delete(&mut self, task: &Task) -> Result<(), ProgressError> {
+        self.begin()?;
+        let deleted = self
+            .connection
+            .execute("DELETE FROM tasks WHERE id = ?1", params![task.id])?;
+        if deleted == 0 {
+            return Err(ProgressError::NotFound(task.key()));
+        }
+        return Ok(());
+    }
+
+    fn query(&self, query: &TaskQuery) -> Result<Vec<Task>, ProgressError> {
+        let mut sql = format!("SELECT {} FROM tasks WHERE 1 = 1", TASK_COLUMNS);
+        let mut values: Vec<i64> = vec![];
+
+        if let Some(done) = query.done {
+            values.push(done as i64);
+            sql.push_str(&format!(" AND done = ?{}", values.len()));
+        }
+        if let Some(from) = query.created_from {
+            values.push(from);
+            sql.push_str(&format!(" AND date_created >= ?{}", values.len()));
+        }
+        if let Some(until) = query.created_until {
+            values.push(until);
+            sql.push_str(&format!(" AND date_created < ?{}", values.len()));
+        }
+        sql.push_str(" ORDER BY id");
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let tasks = statement
+            .query_map(params_from_iter(values), SqliteRepository::read_task)?
+            .collect::<rusqlite::Result<Vec<Task>>>()?;
+        return Ok(tasks);
+    }
+
+    fn commit(&mut self, metadata: &Metadata) -> Result<(), ProgressError> {
+        self.begin()?;
+        self.connection.execute(
+            "INSERT INTO metadata (key, value) VALUES ('last_task_id', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![metadata.last_task_id],
+        )?;
+        // projects are few and only change through metadata, so they're rewritten
+        self.connection.execute("DELETE FROM projects", [])?;
+        for project in &metadata.projects {
+            self.connection.execute(
+                "INSERT INTO projects (name, prefix, archived) VALUES (?1, ?2, ?3)",
+                params![project.name, project.prefix, project.archived],
+            )?;
+        }
+        self.connection.execute_batch("COMMIT")?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ds::tests::TempStore;
+
+    #[test]
+    fn changes_survive_reopening() {
+        let temp = TempStore::named("progress.db");
+        let path = &temp.path;
+        let task = |id: u32, label: &str| Task {
+            id,
+            label: label.to_string(),
+            ..Task::default()
+        };
+
+        let mut repository = SqliteRepository::open(path, true).unwrap();
+        repository.insert(&task(0, "first")).unwrap();
+        repository.insert(&task(1, "second")).unwrap();
+        repository.update(&task(1, "renamed")).unwrap();
+        repository.delete(&task(0, "first")).unwrap();
+        let metadata = Metadata {
+            last_task_id: 2,
+            projects: vec![Project {
+                name: "Work".to_string(),
+                prefix: "WORK".to_string(),
+                archived: true,
+            }],
+        };
+        repository.commit(&metadata).unwrap();
+        drop(repository);
+
+        let mut repository = SqliteRepository::open(path, false).unwrap();
+        let (loaded, tasks) = repository.load().unwrap();
+
+        // there was nothing to back up
+        assert!(!sibling_path(path, "v0.bak").exists());
+        assert_eq!(loaded, metadata);
+        assert_eq!(tasks, vec![task(1, "renamed")]);
+    }
+
+    #[test]
+    fn only_writers_upgrade_the_file() {
+        let temp = TempStore::named("progress.db");
+        let path = &temp.path;
+        let backup = sibling_path(path, "v5.bak");
+        let connection = Connection::open(path).unwrap();
+        for migration in &SCHEMA_MIGRATIONS[..5] {
+            connection.execute_batch(migration).unwrap();
+        }
+        connection
+            .execute_batch(
+                "INSERT INTO tasks (id, done, label, date_created) VALUES (0, 0, 'old', 100);
+                 PRAGMA user_version = 5;",
+            )
+            .unwrap();
+        drop(connection);
+        let file_version = || {
+            let connection = Connection::open(path).unwrap();
+            schema_version(&connection).unwrap()
+        };
+
+        let (_, tasks) = SqliteRepository::open(path, false).unwrap().load().unwrap();
+        assert_eq!(tasks[0].label, "old");
+        assert_eq!(tasks[0].parent, None);
+        assert_eq!(file_version(), 5);
+        assert!(!backup.exists());
+
+        let (_, upgraded) = SqliteRepository::open(path, true).unwrap().load().unwrap();
+        assert_eq!(upgraded, tasks);
+        assert_eq!(file_version(), SCHEMA_VERSION);
+        let connection = Connection::open(&backup).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), 5);
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::ds::{
+    sibling_path, Metadata, Project, Task, METADATA_FIELDS, PROJECT_FIELDS, TASK_FIELDS,
+};
+use crate::error::ProgressError;
+use crate::migrations::{self, STORE_VERSION};
+use crate::repository::{TaskQuery, TaskRepository};
+
+enum ParseState {
+    Metadata(u8),
+    Task(u8),
+    Project(u8),
+}
+
+/// The original `progress.store` text format, rewritten in full on commit
+pub struct TextRepository {
+    path: PathBuf,
+    writable: bool,
+    tasks: Vec<Task>,
+}
+
+impl TextRepository {
+    pub fn new(path: &Path, writable: bool) -> Self {
+        return TextRepository {
+            path: path.to_path_buf(),
+            writable,
+            tasks: vec![],
+        };
+    }
+
+    /// A repository that replaces whatever is on disk with `tasks` on commit
+    pub fn with_tasks(path: &Path, tasks: Vec<Task>) -> Self {
+        return TextRepository {
+            path: path.to_path_buf(),
+            writable: true,
+            tasks,
+        };
+    }
+
+    /// Renders a whole store file, version header included
+    pub fn render(metadata: &Metadata, tasks: &[Task]) -> String {
+        let mut content_buffer = String::new();
+
+        // dump metadata (and the format version header)
+        metadata.dump(&mut content_buffer);
+        tasks.iter().for_each(|task| task.dump(&mut content_buffer));
+        return content_buffer;
+    }
+
+    /// Parses the body (everything after the version header) of a store in
+    /// the current format. `line_numbers` has the file line of each body line,
+    /// as `migrations::migrate` gives them.
+    pub fn parse(
+        content: &str,
+        line_numbers: &[usize],
+    ) -> Result<(Metadata, Vec<Task>), ProgressError> {
+        // parse content
+        let lines = content.split('\n').collect::<Vec<&str>>();
+        let mut metadata = Metadata::default();
+        let mut state: Option<ParseState> = None;
+        let mut tasks: Vec<Task> = vec![];
+
+        for (index, line) in lines.into_iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let fail = |reason: String| ProgressError::Parse {
+                line: line_numbers[index],
+                content: line.to_string(),
+                reason,
+            };
+
+            match line {
+                ":metadata" | ":task" | ":project" if state.is_some() => {
+                    return Err(fail(
+                        "Block starts before the previous one ended".to_string(),
+                    ));
+                }
+                ":metadata" => {
+                    state = Some(ParseState::Metadata(0));
+                }
+                ":task" => {
+                    state = Some(ParseState::Task(0));
+                    let task = Task::default();
+                    tasks.push(task);
+                }
+                ":project" => {
+                    state = Some(ParseState::Project(0));
+                    metadata.projects.push(Project::default());
+                }
+                ":end" => {
+                    // ensure the pointers ended well
+                    if let Some(s) = state {
+                        match s {
+                            ParseState::Metadata(n) => {
+                                if n != METADATA_FIELDS {
+                                    return Err(fail("Premature eol for metadata".to_string()));
+                                }
+                            }
+                            ParseState::Task(n) => {
+                                if n != TASK_FIELDS {
+                                    return Err(fail("Premature eol for task".to_string()));
+                                }
+                            }
+                            ParseState::Project(n) => {
+                                if n != PROJECT_FIELDS {
+                                    return Err(fail("Premature eol for project".to_string()));
+                                }
+                            }
+                        }
+                    }
+                    state = None;
+                }
+                _ => {
+                    let status = state.take();
+                    if let Some(mut status) = status {
+                        match status {
+                            ParseState::Metadata(pointer) => {
+                                metadata.read_field(pointer, line).map_err(fail)?;
+                                status = ParseState::Metadata(pointer + 1);
+                            }
+                            ParseState::Task(pointer) => {
+                                let task = tasks.last_mut().expect(":task pushes a task");
+                                task.read_field(pointer, line).map_err(fail)?;
+                                status = ParseState::Task(pointer + 1);
+                            }
+                            ParseState::Project(pointer) => {
+                                let project = metadata
+                                    .projects
+                                    .last_mut()
+                                    .expect(":project pushes a project");
+                                project.read_field(pointer, line).map_err(fail)?;
+                                status = ParseState::Project(pointer + 1);
+                            }
+                        }
+                        state = Some(status);
+                    }
+                }
+            }
+        }
+
+        if state.is_some() {
+            let last_line = content.trim_end_matches('\n').split('\n').count();
+            return Err(ProgressError::Parse {
+                line: line_numbers[last_line - 1],
+                content: String::new(),
+                reason: "Store ends before the last block's :end".to_string(),
+            });
+        }
+
+        return Ok((metadata, tasks));
+    }
+
+    /// Writes the store to a temporary file next to it and renames it over
+    /// the original, so an interrupted save never leaves a half-written store.
+    /// The previous version is kept as `progress.store.bak`.
+    fn write(&self, content_buffer: &str) -> io::Result<()> {
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(directory)?;
+
+        let temp_path = sibling_path(&self.path, &format!("tmp-{}", process::id()));
+        let written = (|| {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(content_buffer.as_bytes())?;
+            file.sync_all()
+        })();
+
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        let replaced = (|| {
+            if self.path.exists() {
+                fs::copy(&self.path, sibling_path(&self.path, "bak"))?;
+            }
+            fs::rename(&temp_path, &self.path)
+        })();
+
+        if let Err(e) = replaced {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        // make the rename itself durable
+        #[cfg(unix)]
+        fs::File::open(directory)?.sync_all()?;
+
+        return Ok(());
+    }
+}
+
+impl TaskRepository for TextRepository {
+    fn load(&mut self) -> Result<(Metadata, Vec<Task>), ProgressError> {
+        if !self.path.exists() {
+            self.tasks = vec![];
+            return Ok((Metadata::default(), vec![]));
+        }
+
+        let buf = fs::read_to_string(&self.path)?;
+        let (version, body, first_line) = migrations::split_header(&buf)?;
+        if version > STORE_VERSION {
+            return Err(ProgressError::UnsupportedVersion {
+                found: version,
+                supported: STORE_VERSION,
+            });
+        }
+
+        let migrated = version < STORE_VERSION;
+        let (body, line_numbers) = migrations::migrate(body, version, first_line)?;
+        let (metadata, tasks) = TextRepository::parse(&body, &line_numbers)?;
+
+        // upgrade the file when we're allowed to write to it, read-only
+        // commands keep working off the migrated copy in memory
+        if migrated && self.writable {
+            fs::copy(
+                &self.path,
+                sibling_path(&self.path, &format!("v{}.bak", version)),
+            )?;
+            self.write(&TextRepository::render(&metadata, &tasks))?;
+        }
+
+        self.tasks = tasks.clone();
+        return Ok((metadata, tasks));
+    }
+
+    fn insert(&mut self, task: &Task) -> Result<(), ProgressError> {
+        self.tasks.push(task.clone());
+        return Ok(());
+    }
+
+    fn update(&mut self, task: &Task) -> Result<(), ProgressError> {
+        let existing = self
+            .tasks
+            .iter_mut()
+            .find(|existing| existing.id == task.id)
+            .ok_or_else(|| ProgressError::NotFound(task.key()))?;
+        *existing = task.clone();
+        return Ok(());
+    }
+
+    fn delete(&mut self, task: &Task) -> Result<(), ProgressError> {
+        let index = self
+            .tasks
+            .iter()
+            .position(|existing| existing.id == task.id)
+            .ok_or_else(|| ProgressError::NotFound(task.key()))?;
+        self.tasks.swap_remove(index);
+        return Ok(());
+    }
+
+    fn query(&self, query: &TaskQuery) -> Result<Vec<Task>, ProgressError> {
+        let mut tasks = self
+            .tasks
+            .iter()
+            .filter(|task| query.matches(task))
+            .cloned()
+            .collect::<Vec<Task>>();
+        tasks.sort_by_key(|task| task.id);
+        return Ok(tasks);
+    }
+
+    fn commit(&mut self, metadata: &Metadata) -> Result<(), ProgressError> {
+        self.write(&TextRepository::render(metadata, &self.tasks))?;
+        return Ok(());
+    }
+}
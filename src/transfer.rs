@@ -0,0 +1,469 @@
+use std::path::Path;
+
+use chrono::DateTime;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::ds::{split_ids, Metadata, Priority, Task};
+use crate::error::ProgressError;
+use crate::utils::{parse_prefix, parse_tag};
+
+const CSV_COLUMNS: [&str; 11] = [
+    "id",
+    "done",
+    "label",
+    "date_created",
+    "date_checked",
+    "priority",
+    "date_due",
+    "tags",
+    "project",
+    "parent",
+    "blocked_by",
+];
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// Guesses the format from a file's extension
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?;
+        return Format::from_str(extension, true).ok();
+    }
+}
+
+/// The whole store as one JSON document
+#[derive(Serialize, Deserialize)]
+struct Document {
+    metadata: Metadata,
+    tasks: Vec<Task>,
+}
+
+pub fn export(metadata: &Metadata, tasks: &[Task], format: Format) -> String {
+    match format {
+        Format::Json => {
+            let document = Document {
+                metadata: metadata.clone(),
+                tasks: tasks.to_vec(),
+            };
+            let mut json = serde_json::to_string_pretty(&document).expect("tasks serialize");
+            json.push('\n');
+            return json;
+        }
+        Format::Csv => {
+            let mut csv = CSV_COLUMNS.join(",");
+            csv.push('\n');
+            for task in tasks {
+                let date_checked = task
+                    .date_checked
+                    .map(|date| date.to_string())
+                    .unwrap_or_default();
+                let date_due = task
+                    .date_due
+                    .map(|date| date.to_string())
+                    .unwrap_or_default();
+                let fields = [
+                    task.id.to_string(),
+                    task.done.to_string(),
+                    csv_field(&task.label),
+                    task.date_created.to_string(),
+                    date_checked,
+                    task.priority.name().to_string(),
+                    date_due,
+                    csv_field(&task.joined_tags()),
+                    task.project.clone().unwrap_or_default(),
+                    task.parent
+                        .map(|parent| parent.to_string())
+                        .unwrap_or_default(),
+                    task.joined_blocked_by(),
+                ];
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+            return csv;
+        }
+    }
+}
+
+/// Reads an export back. CSV files carry no metadata, so the store works out
+/// `last_task_id` from the tasks themselves.
+pub fn import(
+    content: &str,
+    format: Format,
+) -> Result<(Option<Metadata>, Vec<Task>), ProgressError> {
+    match format {
+        Format::Json => {
+            let mut document: Document = serde_json::from_str(content)
+                .map_err(|e| ProgressError::Import(format!("line {}: {}", e.line(), e)))?;
+            // hold the fields to the same rules the store reads them back with
+            for project in document.metadata.projects.iter_mut() {
+                project.prefix = parse_prefix(&project.prefix).map_err(|e| {
+                    ProgressError::Import(format!("project \"{}\": {}", project.name, e))
+                })?;
+            }
+            for task in document.tasks.iter_mut() {
+                let id = task.id;
+                let fail = |e: ProgressError| ProgressError::Import(format!("task {}: {}", id, e));
+                task.tags = task
+                    .tags
+                    .iter()
+                    .map(|tag| parse_tag(tag).map_err(fail))
+                    .collect::<Result<_, _>>()?;
+                task.project = match &task.project {
+                    Some(prefix) => Some(parse_prefix(prefix).map_err(fail)?),
+                    None => None,
+                };
+
+                let dates = [
+                    ("date_created", Some(task.date_created)),
+                    ("date_checked", task.date_checked),
+                    ("date_due", task.date_due),
+                ];
+                for (name, date) in dates {
+                    if date.is_some_and(|date| !is_valid_timestamp(date)) {
+                        return Err(ProgressError::Import(format!(
+                            "task {} has an out of range {}",
+                            task.id, name
+                        )));
+                    }
+                }
+            }
+            return Ok((Some(document.metadata), document.tasks));
+        }
+        Format::Csv => return Ok((None, read_csv(content)?)),
+    }
+}
+
+/// Whether the timestamp is a date the rest of `progress` can work with
+fn is_valid_timestamp(timestamp: i64) -> bool {
+    return DateTime::from_timestamp(timestamp, 0).is_some();
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.starts_with(' ') || value.ends_with(' ') {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
+
+/// Splits CSV into records of fields, with the line each record starts on
+fn csv_records(content: &str) -> Result<Vec<(usize, Vec<String>)>, ProgressError> {
+    let mut records = vec![];
+    let mut record: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                c => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(ProgressError::Import(format!(
+            "line {}: quoted field is never closed",
+            record_line
+        )));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+
+    // blank lines aren't records
+    records.retain(|(_, record)| !(record.len() == 1 && record[0].is_empty()));
+    return Ok(records);
+}
+
+fn read_csv(content: &str) -> Result<Vec<Task>, ProgressError> {
+    let mut records = csv_records(content)?.into_iter();
+    let (_, header) = records
+        .next()
+        .ok_or_else(|| ProgressError::Import("empty CSV file".to_string()))?;
+
+    // columns may come in any order, only the first four are required
+    let mut columns = [None; CSV_COLUMNS.len()];
+    for (index, name) in header.iter().enumerate() {
+        if let Some(column) = CSV_COLUMNS.iter().position(|column| column == &name.trim()) {
+            columns[column] = Some(index);
+        }
+    }
+    for (column, name) in columns.iter().zip(CSV_COLUMNS).take(4) {
+        if column.is_none() {
+            return Err(ProgressError::Import(format!(
+                "CSV header has no {} column",
+                name
+            )));
+        }
+    }
+
+    let mut tasks = vec![];
+    for (line, record) in records {
+        let fail = |reason: String| ProgressError::Import(format!("line {}: {}", line, reason));
+        let value = |column: usize| -> &str {
+            columns[column]
+                .and_then(|index| record.get(index))
+                .map(|value| value.as_str())
+                .unwrap_or("")
+        };
+
+        let id = value(0)
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| fail(format!("invalid id \"{}\"", value(0))))?;
+        let done = match value(1).trim().to_lowercase().as_str() {
+            "true" | "1" | "x" => true,
+            "false" | "0" | "" => false,
+            other => return Err(fail(format!("invalid done value \"{}\"", other))),
+        };
+        let date_created = value(3)
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .filter(|date| is_valid_timestamp(*date))
+            .ok_or_else(|| fail(format!("invalid date_created \"{}\"", value(3))))?;
+        let date_checked = match value(4).trim() {
+            "" => None,
+            date => Some(
+                date.parse::<i64>()
+                    .ok()
+                    .filter(|date| is_valid_timestamp(*date))
+                    .ok_or_else(|| fail(format!("invalid date_checked \"{}\"", date)))?,
+            ),
+        };
+        let priority = match value(5).trim() {
+            "" => Priority::None,
+            name => Priority::from_name(name)
+                .ok_or_else(|| fail(format!("invalid priority \"{}\"", name)))?,
+        };
+        let date_due = match value(6).trim() {
+            "" => None,
+            date => Some(
+                date.parse::<i64>()
+                    .ok()
+                    .filter(|date| is_valid_timestamp(*date))
+                    .ok_or_else(|| fail(format!("invalid date_due \"{}\"", date)))?,
+            ),
+        };
+        let tags = value(7)
+            .split_whitespace()
+            .map(|tag| parse_tag(tag).map_err(|e| fail(e.to_string())))
+            .collect::<Result<_, _>>()?;
+        let project = match value(8).trim() {
+            "" => None,
+            prefix => Some(parse_prefix(prefix).map_err(|e| fail(e.to_string()))?),
+        };
+
+        let parent = match value(9).trim() {
+            "" => None,
+            parent => Some(
+                parent
+                    .parse::<u32>()
+                    .map_err(|_| fail(format!("invalid parent \"{}\"", parent)))?,
+            ),
+        };
+        let blocked_by = split_ids(value(10))
+            .ok_or_else(|| fail(format!("invalid blocked_by \"{}\"", value(10))))?;
+
+        tasks.push(Task {
+            id,
+            done,
+            label: value(2).to_string(),
+            date_created,
+            date_checked,
+            priority,
+            date_due,
+            tags,
+            project,
+            parent,
+            blocked_by,
+        });
+    }
+
+    return Ok(tasks);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::ds::Project;
+
+    fn tasks() -> Vec<Task> {
+        return vec![
+            Task {
+                id: 0,
+                done: true,
+                label: "Plain".to_string(),
+                date_created: 100,
+                date_checked: Some(200),
+                ..Task::default()
+            },
+            Task {
+                id: 3,
+                label: "Commas, \"quotes\" and\na second line".to_string(),
+                date_created: 300,
+                priority: Priority::High,
+                date_due: Some(400),
+                tags: BTreeSet::from(["home".to_string(), "urgent".to_string()]),
+                project: Some("WORK".to_string()),
+                parent: Some(0),
+                blocked_by: BTreeSet::from([0, 1]),
+                ..Task::default()
+            },
+            Task {
+                id: 4,
+                label: " padded ".to_string(),
+                date_created: 500,
+                ..Task::default()
+            },
+        ];
+    }
+
+    fn import_error(content: &str, format: Format) -> String {
+        return match import(content, format) {
+            Ok(_) => panic!("{:?} imported", content),
+            Err(e) => e.to_string(),
+        };
+    }
+
+    #[test]
+    fn exports_round_trip() {
+        let metadata = Metadata {
+            last_task_id: 5,
+            ..Metadata::default()
+        };
+        for format in [Format::Json, Format::Csv] {
+            let (imported_metadata, imported) =
+                import(&export(&metadata, &tasks(), format), format).unwrap();
+            assert_eq!(imported, tasks());
+            match format {
+                Format::Json => assert_eq!(imported_metadata, Some(metadata.clone())),
+                Format::Csv => assert_eq!(imported_metadata, None),
+            }
+        }
+    }
+
+    #[test]
+    fn csv_columns_can_be_reordered_or_left_out() {
+        let csv = "label,date_created,id,done\r\n\"a, b\",100,7,x\r\n\r\nc,200,8,\r\n";
+        let (_, tasks) = import(csv, Format::Csv).unwrap();
+        let read: Vec<(u32, bool, &str)> = tasks
+            .iter()
+            .map(|task| (task.id, task.done, task.label.as_str()))
+            .collect();
+        assert_eq!(read, [(7, true, "a, b"), (8, false, "c")]);
+    }
+
+    #[test]
+    fn malformed_csv_is_rejected_with_its_line() {
+        assert_eq!(
+            import_error("", Format::Csv),
+            "Could not import tasks: empty CSV file"
+        );
+        assert_eq!(
+            import_error("id,done,label\n", Format::Csv),
+            "Could not import tasks: CSV header has no date_created column"
+        );
+
+        let cases = [
+            (
+                "0,false,\"never closed,100\n",
+                "line 2: quoted field is never closed",
+            ),
+            ("zero,false,a,100\n", "line 2: invalid id \"zero\""),
+            ("0,maybe,a,100\n", "line 2: invalid done value \"maybe\""),
+            (
+                "0,false,\"two\nlines\",100\n1,false,b,\n",
+                "line 4: invalid date_created \"\"",
+            ),
+            (
+                "0,false,a,100,99999999999999999\n",
+                "line 2: invalid date_checked \"99999999999999999\"",
+            ),
+        ];
+        for (rows, reason) in cases {
+            let content = format!("id,done,label,date_created,date_checked\n{}", rows);
+            assert_eq!(
+                import_error(&content, Format::Csv),
+                format!("Could not import tasks: {}", reason)
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_rejected() {
+        assert!(import_error("{\"tasks\": [", Format::Json)
+            .starts_with("Could not import tasks: line 1"));
+
+        let metadata = Metadata::default();
+        let mut task = tasks().remove(0);
+        task.date_due = Some(i64::MAX);
+        let json = export(&metadata, &[task], Format::Json);
+        assert_eq!(
+            import_error(&json, Format::Json),
+            "Could not import tasks: task 0 has an out of range date_due"
+        );
+
+        // anything the store couldn't read back is refused up front
+        let mut task = tasks().remove(0);
+        task.tags = BTreeSet::from(["two words".to_string()]);
+        assert_eq!(
+            import_error(&export(&metadata, &[task], Format::Json), Format::Json),
+            "Could not import tasks: task 0: Invalid tag \"two words\", use letters, digits, -, _ or /"
+        );
+        let mut task = tasks().remove(0);
+        task.project = Some("no good".to_string());
+        assert_eq!(
+            import_error(&export(&metadata, &[task], Format::Json), Format::Json),
+            "Could not import tasks: task 0: Invalid prefix \"no good\", use up to 8 letters and digits starting with a letter"
+        );
+        let metadata = Metadata {
+            projects: vec![Project {
+                name: "Side".to_string(),
+                prefix: "SIDE GIG".to_string(),
+                archived: false,
+            }],
+            ..Metadata::default()
+        };
+        assert_eq!(
+            import_error(&export(&metadata, &[], Format::Json), Format::Json),
+            "Could not import tasks: project \"Side\": Invalid prefix \"SIDE GIG\", use up to 8 letters and digits starting with a letter"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::ds::{Priority, Store, Task};
+use crate::error::ProgressError;
+use crate::lock::LockMode;
+use crate::report::Overview;
+use crate::utils::format_timestamp_ago;
+
+const PANES: [&str; 3] = ["Today", "Carry-over", "Done"];
+const TODAY: usize = 0;
+const CARRY_OVER: usize = 1;
+const DONE: usize = 2;
+
+const HELP: &str = "space check/uncheck · a add · r rename · d remove · tab switch pane · q quit";
+
+enum Mode {
+    Normal,
+    Add(String),
+    /// The task's key, its id and the label being typed
+    Rename(String, u32, String),
+    /// The task's key and id
+    ConfirmRemove(String, u32),
+}
+
+struct App {
+    panes: [Vec<Task>; 3],
+    states: [ListState; 3],
+    focus: usize,
+    mode: Mode,
+    /// What the last change did, and whether it failed
+    status: Option<(String, bool)>,
+    quit: bool,
+}
+
+/// Runs the full-screen view until the user quits. Every change is saved as
+/// soon as it's made.
+pub fn run(store: &mut Store) -> Result<(), ProgressError> {
+    let mut app = App {
+        panes: [vec![], vec![], vec![]],
+        states: Default::default(),
+        focus: TODAY,
+        mode: Mode::Normal,
+        status: None,
+        quit: false,
+    };
+    app.refresh(store)?;
+    // changes lock the store again, see `apply`
+    store.unlock();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal, store);
+    // put the terminal back even when the loop failed
+    let restored = ratatui::try_restore();
+    result?;
+    restored?;
+    return Ok(());
+}
+
+impl App {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        store: &mut Store,
+    ) -> Result<(), ProgressError> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key.code, store);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Reloads the panes from the store, the same way the overview does
+    fn refresh(&mut self, store: &Store) -> Result<(), ProgressError> {
+        let overview = Overview::build(store)?;
+        let mut done: Vec<Task> = store
+            .visible_tasks()
+            .filter(|task| task.done && !overview.today.iter().any(|today| today.id == task.id))
+            .cloned()
+            .collect();
+        done.sort_by_key(|task| std::cmp::Reverse(task.date_checked));
+
+        self.panes = [overview.today, overview.carry_over, done];
+        for (pane, state) in self.panes.iter().zip(self.states.iter_mut()) {
+            let selected = match (state.selected(), pane.len()) {
+                (_, 0) => None,
+                (Some(index), length) => Some(index.min(length - 1)),
+                (None, _) => Some(0),
+            };
+            state.select(selected);
+        }
+        return Ok(());
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        let index = self.states[self.focus].selected()?;
+        return self.panes[self.focus].get(index);
+    }
+
+    /// Makes a change, saves it and reports how it went in the status line
+    fn apply(
+        &mut self,
+        store: &mut Store,
+        change: impl FnOnce(&mut Store) -> Result<String, ProgressError>,
+    ) {
+        let outcome = store.locked(LockMode::Exclusive, |store| {
+            let message = change(store)?;
+            store.save()?;
+            Ok(message)
+        });
+        self.status = Some(match outcome {
+            Ok(message) => (message, false),
+            Err(e) => (e.to_string(), true),
+        });
+        if let Err(e) = self.refresh(store) {
+            self.status = Some((e.to_string(), true));
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyCode, store: &mut Store) {
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => self.handle_normal_key(key, store),
+            Mode::Add(mut label) => match key {
+                KeyCode::Enter if !label.trim().is_empty() => self.apply(store, |store| {
+                    let id = store.create_task(Task {
+                        label,
+                        ..Task::default()
+                    })?;
+                    Ok(format!("Task {} added", store.task_key(id)))
+                }),
+                KeyCode::Esc | KeyCode::Enter => {}
+                key => {
+                    edit(&mut label, key);
+                    self.mode = Mode::Add(label);
+                }
+            },
+            Mode::Rename(task_key, id, mut label) => match key {
+                KeyCode::Enter if !label.trim().is_empty() => self.apply(store, |store| {
+                    store.relabel_task(id, &label)?;
+                    Ok(format!("Task {} renamed", store.task_key(id)))
+                }),
+                KeyCode::Esc | KeyCode::Enter => {}
+                key => {
+                    edit(&mut label, key);
+                    self.mode = Mode::Rename(task_key, id, label);
+                }
+            },
+            Mode::ConfirmRemove(_, id) => {
+                if key == KeyCode::Char('y') {
+                    self.apply(store, |store| {
+                        let key = store.task_key(id);
+                        store.remove_task(id)?;
+                        Ok(format!("Task {} removed", key))
+                    });
+                }
+            }
+        }
+    }
+
+    fn handle_normal_key(&mut self, key: KeyCode, store: &mut Store) {
+        self.status = None;
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.focus = (self.focus + 1) % PANES.len();
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.focus = (self.focus + PANES.len() - 1) % PANES.len();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.states[self.focus].select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.states[self.focus].select_previous(),
+            KeyCode::Char('a') => self.mode = Mode::Add(String::new()),
+            KeyCode::Char(' ') | KeyCode::Char('x') | KeyCode::Enter => {
+                if let Some(task) = self.selected() {
+                    let (id, check) = (task.id, !task.done);
+                    self.apply(store, |store| {
+                        let checked = store.toggle_check_task(id, check)?;
+                        let done = if check { "checked" } else { "unchecked" };
+                        let mut message = format!("Task {} {}", store.task_key(id), done);
+                        if let Some(warning) = store.blocked_warning(&checked) {
+                            message += &format!(", it {}", warning);
+                        }
+                        Ok(message)
+                    });
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(task) = self.selected() {
+                    self.mode = Mode::Rename(task.key(), task.id, task.label.clone());
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(task) = self.selected() {
+                    self.mode = Mode::ConfirmRemove(task.key(), task.id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let areas: [_; 3] = Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(main);
+
+        for (index, area) in areas.into_iter().enumerate() {
+            let items: Vec<ListItem> = self.panes[index]
+                .iter()
+                .map(|task| item(task, index))
+                .collect();
+            let mut block = Block::bordered().title(format!(
+                " {} ({}) ",
+                PANES[index],
+                self.panes[index].len()
+            ));
+            let mut list = List::new(items);
+            if index == self.focus {
+                block = block.border_style(Style::new().yellow());
+                list = list.highlight_style(Style::new().reversed());
+            }
+            frame.render_stateful_widget(list.block(block), area, &mut self.states[index]);
+        }
+
+        let line = match &self.mode {
+            Mode::Add(label) => Line::from(format!("New task: {}▏", label)),
+            Mode::Rename(key, _, label) => Line::from(format!("Rename {}: {}▏", key, label)),
+            Mode::ConfirmRemove(key, _) => Line::from(format!("Remove {}? (y/n)", key)).red(),
+            Mode::Normal => match &self.status {
+                Some((message, true)) => Line::from(message.as_str()).red(),
+                Some((message, false)) => Line::from(message.as_str()).green(),
+                None => Line::from(HELP).dim(),
+            },
+        };
+        frame.render_widget(Paragraph::new(line), status);
+    }
+}
+
+fn item(task: &Task, pane: usize) -> ListItem<'_> {
+    let mut spans = vec![];
+    if task.done {
+        spans.push(Span::from(format!("{} [x] ", task.key())).green());
+    } else {
+        spans.push(Span::from(format!("{} [ ] ", task.key())).red());
+    }
+    match task.priority {
+        Priority::High => spans.push(Span::from("!!! ").red().bold()),
+        Priority::Medium => spans.push(Span::from("!! ").yellow().bold()),
+        Priority::Low => spans.push(Span::from("! ").blue()),
+        Priority::None => {}
+    }
+    if task.done {
+        spans.push(Span::from(task.label.as_str()).crossed_out());
+    } else {
+        spans.push(Span::from(task.label.as_str()));
+    }
+    for tag in &task.tags {
+        spans.push(Span::from(format!(" #{}", tag)).cyan());
+    }
+
+    let when = match (pane, task.date_checked) {
+        (CARRY_OVER, _) => Some(format_timestamp_ago(task.date_created)),
+        (DONE, Some(date_checked)) => Some(format_timestamp_ago(date_checked)),
+        _ => None,
+    };
+    if let Some(when) = when {
+        spans.push(Span::from(format!(" ({})", when)).dim());
+    }
+    return ListItem::new(Line::from(spans));
+}
+
+/// Typing and backspace in the input line
+fn edit(text: &mut String, key: KeyCode) {
+    match key {
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::Backspace => {
+            text.pop();
+        }
+        _ => {}
+    }
+}
@@ -1,57 +1,200 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, SubsecRound, TimeDelta, Utc};
+use std::fmt;
 
+use crate::ds::Task;
+use crate::error::ProgressError;
+
+/// "3 days ago" for past timestamps, "in 3 days" for future ones
 pub fn format_timestamp_ago(timestamp: i64) -> String {
-    let now = Local::now().to_utc();
-    let time = DateTime::from_timestamp(timestamp, 0).unwrap();
+    // whole seconds, so "in 3 days" isn't a few milliseconds short of it
+    let now = Utc::now().trunc_subsecs(0);
+    let time = match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time,
+        None => return "at an unknown time".to_string(),
+    };
 
     let duration = now - time;
+    if duration < TimeDelta::zero() {
+        return format!("in {}", format_duration(-duration));
+    }
+    return format!("{} ago", format_duration(duration));
+}
 
+fn format_duration(duration: TimeDelta) -> String {
     if duration.num_seconds() < 60 {
-        return format!("{} seconds ago", duration.num_seconds());
+        return format!("{} seconds", duration.num_seconds());
     } else if duration.num_minutes() < 60 {
-        return format!("{} minutes ago", duration.num_minutes());
+        return format!("{} minutes", duration.num_minutes());
     } else if duration.num_hours() < 24 {
-        return format!("{} hours ago", duration.num_hours());
+        return format!("{} hours", duration.num_hours());
     } else if duration.num_days() < 7 {
-        return format!("{} days ago", duration.num_days());
+        return format!("{} days", duration.num_days());
     } else {
-        return format!("{} weeks ago", duration.num_weeks());
-    }
-}
-
-pub fn print_help(name: &String) {
-    println!("{} <command> [options]\n", name);
-    println!("Commands:");
-    println!("  --help            Show this help message.");
-    println!("  --minimal         Show minimal task information.");
-    println!("  --add <label>  Add a new task with the specified label.");
-    println!("  --task <task-id> <command> [options]  Manage an existing task.\n");
-    println!("Task Commands:");
-    println!("  --remove          Remove the task with the given ID.");
-    println!("  --rename <label>  Renames the task with the given ID");
-    println!("  --check           Mark the task with the given ID as done.");
-    println!("  --uncheck         Mark the task with the given ID as undone.\n");
-    println!("Examples:");
-    println!(
-        "  {} --help                          Show this help message.",
-        name
-    );
-    println!(
-        "  {} --minimal                       Show minimal task information.",
-        name
-    );
-    println!("  {} --add \"Buy groceries\"       Add a new task.", name);
-    println!(
-        "  {} --task TSK-1 --check              Mark task TSK-1 as done.",
-        name
-    );
-    println!(
-        "  {} --task TSK-2 --uncheck            Mark task TSK-2 as undone.",
-        name
-    );
-    println!(
-        "  {} --task TSK-3 --remove             Remove task TSK-3.",
-        name
-    );
-    println!("\n\nwith ❤️ from rubbie kelvin (dev.rubbie@gmail.com)\n");
+        return format!("{} weeks", duration.num_weeks());
+    }
+}
+
+/// A task id as it was typed, like `WORK-3`. `Store::resolve_task` finds the
+/// task, which has to be in the project the prefix names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskId {
+    pub prefix: String,
+    pub number: u32,
+}
+
+impl TaskId {
+    pub fn matches(&self, task: &Task) -> bool {
+        return task.id == self.number && task.prefix() == self.prefix;
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.prefix, self.number)
+    }
+}
+
+/// Parses a task id like `TSK-12` or `work-3` (any case)
+pub fn parse_task_id(id: &str) -> Result<TaskId, ProgressError> {
+    let invalid = || ProgressError::InvalidTaskId(id.to_string());
+    let (prefix, number) = id.split_once('-').ok_or_else(invalid)?;
+
+    return Ok(TaskId {
+        prefix: parse_prefix(prefix).map_err(|_| invalid())?,
+        number: number.parse::<u32>().map_err(|_| invalid())?,
+    });
+}
+
+/// Checks a project prefix like `WORK`, uppercasing it. Prefixes start with a
+/// letter and go on with up to 7 more letters or digits.
+pub fn parse_prefix(value: &str) -> Result<String, ProgressError> {
+    let prefix = value.trim().to_uppercase();
+    let valid = prefix
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic())
+        && prefix.len() <= 8
+        && prefix.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(ProgressError::Usage(format!(
+            "Invalid prefix \"{}\", use up to 8 letters and digits starting with a letter",
+            value
+        )));
+    }
+    return Ok(prefix);
+}
+
+/// A task id, or a range of ids in one project like `TSK-8..TSK-12`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRange {
+    pub start: TaskId,
+    pub end: TaskId,
+}
+
+impl TaskRange {
+    /// Whether the task is in the range's project and between its ends
+    pub fn contains(&self, task: &Task) -> bool {
+        return task.prefix() == self.start.prefix
+            && (self.start.number..=self.end.number).contains(&task.id);
+    }
+}
+
+/// Parses a task id, or a range of them like `TSK-8..TSK-12`
+pub fn parse_task_range(value: &str) -> Result<TaskRange, ProgressError> {
+    let (start, end) = match value.split_once("..") {
+        Some((start, end)) => (parse_task_id(start)?, parse_task_id(end)?),
+        None => {
+            let id = parse_task_id(value)?;
+            (id.clone(), id)
+        }
+    };
+
+    if start.prefix != end.prefix {
+        return Err(ProgressError::Usage(format!(
+            "Range {} spans two projects",
+            value
+        )));
+    }
+    if start.number > end.number {
+        return Err(ProgressError::Usage(format!(
+            "Range {} starts after it ends",
+            value
+        )));
+    }
+    return Ok(TaskRange { start, end });
+}
+
+/// Checks a tag like `infra` or `team/web`, lowercasing it. Tags start with a
+/// letter or digit and go on with letters, digits, `-`, `_` or `/`.
+pub fn parse_tag(value: &str) -> Result<String, ProgressError> {
+    let tag = value.trim().trim_start_matches('+').to_lowercase();
+    let valid = tag.chars().next().is_some_and(|c| c.is_alphanumeric())
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'));
+    if !valid {
+        return Err(ProgressError::Usage(format!(
+            "Invalid tag \"{}\", use letters, digits, -, _ or /",
+            value
+        )));
+    }
+    return Ok(tag);
+}
+
+/// Takes `+tag` words out of a label, so `fix build +infra` is the label
+/// `fix build` tagged `infra`. Words that aren't valid tags stay in the label.
+pub fn split_tags(label: &str) -> (String, Vec<String>) {
+    let mut words = vec![];
+    let mut tags = vec![];
+    for word in label.split(' ') {
+        match word.strip_prefix('+').map(parse_tag) {
+            Some(Ok(tag)) => tags.push(tag),
+            _ => words.push(word),
+        }
+    }
+    return (words.join(" ").trim().to_string(), tags);
+}
+
+/// Wraps a free-text store field in quotes, escaping anything that could
+/// break the line-based format (newlines, quotes and backslashes)
+pub fn quote_field(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+/// Reverses `quote_field`
+pub fn unquote_field(line: &str) -> Result<String, String> {
+    let inner = line
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| format!("Expected a quoted value, found {}", line))?;
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') => value.push('\\'),
+                Some('"') => value.push('"'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some(other) => return Err(format!("Invalid escape \\{} in {}", other, line)),
+                None => return Err(format!("Unfinished escape in {}", line)),
+            },
+            '"' => return Err(format!("Unescaped quote in {}", line)),
+            c => value.push(c),
+        }
+    }
+    return Ok(value);
 }